@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    effects::{Filter, Overlay},
+    screen::ScaleMode,
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "gameboy.json";
+
+//Every binding is a list of key names, see input::parse_key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct JoypadBindings {
+    pub a: Vec<String>,
+    pub b: Vec<String>,
+    pub start: Vec<String>,
+    pub select: Vec<String>,
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct HotkeyBindings {
+    pub quit: Vec<String>,
+    pub pause: Vec<String>,
+    pub reset: Vec<String>,
+    pub fast_forward: Vec<String>,
+    pub screenshot: Vec<String>,
+    pub save_state: Vec<String>,
+    pub load_state: Vec<String>,
+    //One key per slot, slot 0 first
+    pub select_slot: Vec<String>,
+    pub reload_config: Vec<String>,
+    pub next_palette: Vec<String>,
+    pub frame_advance: Vec<String>,
+    pub turbo: Vec<String>,
+    pub slow_motion: Vec<String>,
+    pub toggle_bg: Vec<String>,
+    pub toggle_window: Vec<String>,
+    pub toggle_sprites: Vec<String>,
+    pub tint_layers: Vec<String>,
+    pub toggle_cheats: Vec<String>,
+    pub rewind: Vec<String>,
+}
+
+//Colors are "#RRGGBB", lightest first. OBJ palettes default to the BG colors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PaletteConfig {
+    pub bg: [String; 4],
+    #[serde(default)]
+    pub obj0: Option<[String; 4]>,
+    #[serde(default)]
+    pub obj1: Option<[String; 4]>,
+}
+
+//Window scale and scale mode are only read when the window is created
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub scale: usize,
+    pub scale_mode: ScaleMode,
+    pub filter: Filter,
+    pub overlay: Overlay,
+    pub ghosting: f32,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            scale: 4,
+            scale_mode: ScaleMode::default(),
+            filter: Filter::default(),
+            overlay: Overlay::default(),
+            ghosting: 0.0,
+        }
+    }
+}
+
+//On-screen display elements
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OsdConfig {
+    pub messages: bool,
+    pub fps: bool,
+    pub input: bool,
+    pub frame_counter: bool,
+    //Seconds a message stays on screen
+    pub message_duration: f32,
+}
+
+impl Default for OsdConfig {
+    fn default() -> Self {
+        Self {
+            messages: true,
+            fps: false,
+            input: false,
+            frame_counter: false,
+            message_duration: 2.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RewindConfig {
+    //Frames between snapshots, rewinding steps back this many frames at a time
+    pub interval: u32,
+    //Memory for snapshots in MiB, 0 turns rewind off
+    pub buffer_mb: usize,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            interval: 2,
+            buffer_mb: 64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub joypad: JoypadBindings,
+    pub hotkeys: HotkeyBindings,
+    pub screenshot_dir: String,
+    pub palette: String,
+    pub palettes: BTreeMap<String, PaletteConfig>,
+    pub display: DisplayConfig,
+    pub osd: OsdConfig,
+    pub rewind: RewindConfig,
+    //Speed multiplier of the turbo toggle
+    pub turbo_speed: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            joypad: JoypadBindings::default(),
+            hotkeys: HotkeyBindings::default(),
+            screenshot_dir: String::from("screenshots"),
+            palette: String::from("grayscale"),
+            palettes: BTreeMap::new(),
+            display: DisplayConfig::default(),
+            osd: OsdConfig::default(),
+            rewind: RewindConfig::default(),
+            turbo_speed: 2.0,
+        }
+    }
+}
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+impl Default for JoypadBindings {
+    fn default() -> Self {
+        Self {
+            a: keys(&["Z"]),
+            b: keys(&["X"]),
+            start: keys(&["A"]),
+            select: keys(&["S"]),
+            up: keys(&["Up"]),
+            down: keys(&["Down"]),
+            left: keys(&["Left"]),
+            right: keys(&["Right"]),
+        }
+    }
+}
+
+impl Default for HotkeyBindings {
+    fn default() -> Self {
+        Self {
+            quit: keys(&["Escape"]),
+            pause: keys(&["P"]),
+            reset: keys(&["R"]),
+            fast_forward: keys(&["Tab"]),
+            screenshot: keys(&["F12"]),
+            save_state: keys(&["F2"]),
+            load_state: keys(&["F4"]),
+            select_slot: keys(&[
+                "Key0", "Key1", "Key2", "Key3", "Key4", "Key5", "Key6", "Key7", "Key8", "Key9",
+            ]),
+            reload_config: keys(&["F5"]),
+            next_palette: keys(&["F6"]),
+            frame_advance: keys(&["N"]),
+            turbo: keys(&["T"]),
+            slow_motion: keys(&["M"]),
+            toggle_bg: keys(&["F7"]),
+            toggle_window: keys(&["F8"]),
+            toggle_sprites: keys(&["F9"]),
+            tint_layers: keys(&["F10"]),
+            toggle_cheats: keys(&["F3"]),
+            rewind: keys(&["Backspace"]),
+        }
+    }
+}
+
+//Per rom overrides live next to the rom, e.g. roms/tetris.gb -> roms/tetris.json
+pub fn rom_config_path(rom: &str) -> PathBuf {
+    Path::new(rom).with_extension("json")
+}
+
+fn read_json(path: &Path) -> io::Result<Option<Value>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    serde_json::from_str(&content).map(Some).map_err(|err| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        )
+    })
+}
+
+//Objects are merged key by key so an override only has to list what it changes
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+impl Config {
+    //Missing files are fine and fall back to the defaults
+    pub fn load(path: &str, rom: &str) -> io::Result<Self> {
+        let mut config = serde_json::to_value(Config::default())?;
+
+        for path in [PathBuf::from(path), rom_config_path(rom)] {
+            if let Some(overrides) = read_json(&path)? {
+                info!(target: "Config", "Loaded config from {}", path.display());
+                merge(&mut config, overrides);
+            }
+        }
+
+        serde_json::from_value(config)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))
+    }
+}
@@ -0,0 +1,917 @@
+use std::{io, time::Duration};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::emulator::{
+    bus::Bus,
+    cheats::Cheat,
+    cpu::Cpu,
+    debug::{Access, Breakpoint, Location, StopReason, Watchpoint},
+    gameboy::{Gameboy, CYCLES_PER_FRAME},
+    interrupt::{ADDRESS_IE, ADDRESS_IF, INT_JOYPAD, INT_LCD, INT_SERIAL, INT_TIMER, INT_VBLANK},
+    ppu::{LCDC, LY, LYC, STAT},
+    search::{Comparison, Operand, ValueType},
+    symbols::Symbols,
+    timer::{DIV, TAC, TIMA, TMA},
+};
+
+const DISASSEMBLY_LINES: usize = 24;
+const MEMORY_ROWS: u16 = 16;
+
+const HELP: &str = "s [n] step, n next, c continue, r <addr> run to, b [bank:]<addr> break, bi [n] break on interrupt, bo <op> break on opcode, bs break on call stack mismatch, w <addr>[-end] [r|w|rw] [value] watch, d <addr|all> delete, m <addr> memory, ch [n|add <code> [name]] cheats, rs [u8|i8|u16|i16|bcd8|bcd16] ram search, rf <=|!=|>|<> [value] filter, rc clear search, q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    None,
+    Quit,
+}
+
+//Interactive terminal debugger, drawn next to the game window
+pub struct Debugger {
+    //Taken out while drawing, so the draw functions can borrow the rest
+    terminal: Option<DefaultTerminal>,
+
+    running: bool,
+    //One shot breakpoint used by next and run to
+    temporary: Option<u16>,
+
+    memory_address: u16,
+    input: String,
+    last_command: String,
+    status: String,
+}
+
+fn parse_address(value: &str) -> Option<u16> {
+    let digits = value
+        .trim_start_matches('$')
+        .trim_start_matches("0x")
+        .trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).ok()
+}
+
+//Decimal, or hex with a $ or 0x prefix
+fn parse_value(value: &str) -> Option<i64> {
+    match value.strip_prefix('$').or_else(|| value.strip_prefix("0x")) {
+        Some(digits) => i64::from_str_radix(digits, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_byte(value: &str) -> Option<u8> {
+    parse_address(value).and_then(|value| u8::try_from(value).ok())
+}
+
+//A label from the symbol files, or a hex address
+fn resolve_address(symbols: &Symbols, value: &str) -> Option<u16> {
+    match symbols.find(value) {
+        Some(location) => Some(location.address),
+        None => parse_address(value),
+    }
+}
+
+//"addr", "bank:addr" or a label, which keeps the bank it was defined in
+fn parse_location(symbols: &Symbols, value: &str) -> Option<(Option<usize>, u16)> {
+    if let Some(location) = symbols.find(value) {
+        return Some((location.bank, location.address));
+    }
+
+    match value.split_once(':') {
+        Some((bank, address)) => {
+            let bank = usize::from_str_radix(bank, 16).ok()?;
+            Some((Some(bank), parse_address(address)?))
+        }
+        None => Some((None, parse_address(value)?)),
+    }
+}
+
+//"addr[-end] [r|w|rw] [value]", watching writes by default
+fn parse_watchpoint<'a>(
+    symbols: &Symbols,
+    mut words: impl Iterator<Item = &'a str>,
+) -> Option<Watchpoint> {
+    let range = words.next()?;
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (
+            resolve_address(symbols, start)?,
+            resolve_address(symbols, end)?,
+        ),
+        None => (
+            resolve_address(symbols, range)?,
+            resolve_address(symbols, range)?,
+        ),
+    };
+
+    let mut watchpoint = Watchpoint::new(start, Access::Write);
+    watchpoint.end = end.max(start);
+
+    for word in words {
+        match word {
+            "r" => watchpoint.access = Access::Read,
+            "w" => watchpoint.access = Access::Write,
+            "rw" => watchpoint.access = Access::ReadWrite,
+            value => watchpoint.value = Some(parse_byte(value)?),
+        }
+    }
+
+    Some(watchpoint)
+}
+
+fn format_breakpoint(symbols: &Symbols, breakpoint: &Breakpoint) -> String {
+    if let &Breakpoint::Pc { address, bank } = breakpoint {
+        if let Some(label) = symbols.label(Location { bank, address }) {
+            return label.to_string();
+        }
+    }
+
+    match breakpoint {
+        Breakpoint::Pc {
+            address,
+            bank: Some(bank),
+        } => format!("{:X}:${:04X}", bank, address),
+        Breakpoint::Pc {
+            address,
+            bank: None,
+        } => format!("${:04X}", address),
+        Breakpoint::Interrupt(Some(interrupt)) => format!("int{}", interrupt),
+        Breakpoint::Interrupt(None) => String::from("int"),
+        Breakpoint::Opcode(opcode) => format!("op{:02X}", opcode),
+        Breakpoint::StackMismatch => String::from("stack"),
+    }
+}
+
+fn format_watchpoint(watchpoint: &Watchpoint) -> String {
+    let access = match watchpoint.access {
+        Access::Read => "r",
+        Access::Write => "w",
+        Access::ReadWrite => "rw",
+    };
+    let range = if watchpoint.start == watchpoint.end {
+        format!("${:04X}", watchpoint.start)
+    } else {
+        format!("${:04X}-${:04X}", watchpoint.start, watchpoint.end)
+    };
+
+    match watchpoint.value {
+        Some(value) => format!("{}:{}=${:02X}", range, access, value),
+        None => format!("{}:{}", range, access),
+    }
+}
+
+//"0* CODE name, 1 CODE name", enabled cheats are starred
+fn format_cheats(gameboy: &Gameboy) -> String {
+    if gameboy.cheats().is_empty() {
+        return String::from("No cheats, add one with ch add <code> [name]");
+    }
+
+    gameboy
+        .cheats()
+        .iter()
+        .enumerate()
+        .map(|(index, cheat)| {
+            let enabled = if cheat.enabled { "*" } else { "" };
+            format!("{}{} {} {}", index, enabled, cheat.code, cheat.name)
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn has_breakpoint(gameboy: &Gameboy, address: u16) -> bool {
+    gameboy.breakpoints().iter().any(
+        |breakpoint| matches!(breakpoint, Breakpoint::Pc { address: pc, .. } if *pc == address),
+    )
+}
+
+fn flag(name: &str, set: bool) -> Span<'static> {
+    let style = if set {
+        Style::new().fg(Color::Green).add_modifier(Modifier::BOLD)
+    } else {
+        Style::new().fg(Color::DarkGray)
+    };
+
+    Span::styled(format!("{} ", name), style)
+}
+
+fn bit(value: u8, bit: u8) -> bool {
+    value & (1 << bit) != 0
+}
+
+fn interrupt_flags(value: u8) -> Vec<Span<'static>> {
+    vec![
+        flag("VBL", bit(value, INT_VBLANK)),
+        flag("LCD", bit(value, INT_LCD)),
+        flag("TIM", bit(value, INT_TIMER)),
+        flag("SER", bit(value, INT_SERIAL)),
+        flag("JOY", bit(value, INT_JOYPAD)),
+    ]
+}
+
+//Where a CALL, JP, JR or RST at address goes, to label it
+fn branch_target(bus: &Bus, address: u16) -> Option<u16> {
+    let operand = |offset| bus.peek_byte(address.wrapping_add(offset));
+
+    match bus.peek_byte(address) {
+        0xC3 | 0xC2 | 0xCA | 0xD2 | 0xDA | 0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => {
+            Some(u16::from_le_bytes([operand(1), operand(2)]))
+        }
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => Some(
+            address
+                .wrapping_add(2)
+                .wrapping_add_signed(operand(1) as i8 as i16),
+        ),
+        opcode if opcode & 0xC7 == 0xC7 => Some((opcode & 0x38) as u16),
+        _ => None,
+    }
+}
+
+//Human readable location, with the bank for switchable regions
+fn region(gameboy: &Gameboy, address: u16) -> String {
+    match address {
+        0x0000..=0x3FFF => String::from("ROM0"),
+        0x4000..=0x7FFF => format!("ROM{:X}", gameboy.bus.rom_bank()),
+        0x8000..=0x9FFF => String::from("VRAM"),
+        0xA000..=0xBFFF => format!("SRAM{:X}", gameboy.bus.ram_bank()),
+        0xC000..=0xDFFF => String::from("WRAM"),
+        0xE000..=0xFDFF => String::from("ECHO"),
+        0xFE00..=0xFE9F => String::from("OAM"),
+        0xFEA0..=0xFEFF => String::from("----"),
+        0xFF00..=0xFF7F => String::from("IO"),
+        0xFF80..=0xFFFE => String::from("HRAM"),
+        0xFFFF => String::from("IE"),
+    }
+}
+
+impl Debugger {
+    pub fn new() -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+
+        Ok(Self {
+            terminal: Some(terminal),
+
+            running: false,
+            temporary: None,
+
+            memory_address: 0xC000,
+            input: String::new(),
+            last_command: String::new(),
+            status: String::from(HELP),
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn stop(&mut self, gameboy: &Gameboy, reason: &str) {
+        self.running = false;
+        self.temporary = None;
+        self.status = format!("{} at ${:04X}", reason, gameboy.cpu.borrow().pc);
+    }
+
+    //The reason already names the location for breakpoints
+    fn stop_on(&mut self, gameboy: &Gameboy, reason: StopReason) {
+        match reason {
+            StopReason::Breakpoint { .. } | StopReason::Opcode { .. } => {
+                self.running = false;
+                self.temporary = None;
+                self.status = reason.to_string();
+            }
+            _ => self.stop(gameboy, &reason.to_string()),
+        }
+    }
+
+    fn resume(&mut self) {
+        self.running = true;
+        self.status = String::from("Running");
+    }
+
+    //Runs the rest of the frame, returns false when a breakpoint stopped it
+    pub fn run_frame(&mut self, gameboy: &mut Gameboy) -> bool {
+        if !self.running {
+            return false;
+        }
+
+        while gameboy.accum_cycle < CYCLES_PER_FRAME {
+            if let Some(reason) = gameboy.tick() {
+                self.stop_on(gameboy, reason);
+                return false;
+            }
+
+            if self.temporary.is_some() && self.temporary == Some(gameboy.cpu.borrow().pc) {
+                self.stop(gameboy, "Stopped");
+                return false;
+            }
+        }
+
+        gameboy.accum_cycle -= CYCLES_PER_FRAME;
+        true
+    }
+
+    fn step(&mut self, gameboy: &mut Gameboy, count: u32) {
+        for _ in 0..count {
+            if let Some(reason) = gameboy.tick() {
+                self.stop_on(gameboy, reason);
+                return;
+            }
+        }
+
+        self.stop(gameboy, "Stepped");
+    }
+
+    //Steps over calls by running until the instruction after them
+    fn step_over(&mut self, gameboy: &mut Gameboy) {
+        let pc = gameboy.cpu.borrow().pc;
+        let (instruction, length) = Cpu::disassemble_at(&gameboy.bus, pc);
+
+        if instruction.starts_with("CALL") || instruction.starts_with("RST") {
+            self.temporary = Some(pc.wrapping_add(length));
+            self.resume();
+        } else {
+            self.step(gameboy, 1);
+        }
+    }
+
+    fn execute(&mut self, gameboy: &mut Gameboy, command: &str) -> DebugAction {
+        let command = if command.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            command.trim().to_string()
+        };
+        self.last_command = command.clone();
+
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let argument = words.next();
+        let address = argument.and_then(|value| resolve_address(gameboy.symbols(), value));
+
+        match (name, argument, address) {
+            ("" | "h" | "help", _, _) => self.status = String::from(HELP),
+            ("s" | "step", None, _) => self.step(gameboy, 1),
+            ("s" | "step", Some(count), _) => match count.parse() {
+                Ok(count) => self.step(gameboy, count),
+                Err(_) => self.status = format!("Invalid step count {}", count),
+            },
+            ("n" | "next", _, _) => self.step_over(gameboy),
+            ("c" | "continue", _, _) => self.resume(),
+            ("p" | "pause", _, _) => self.stop(gameboy, "Paused"),
+            ("r" | "run", _, Some(address)) => {
+                self.temporary = Some(address);
+                self.resume();
+            }
+            ("b" | "break", Some(location), _) => match parse_location(gameboy.symbols(), location)
+            {
+                Some((bank, address)) => {
+                    let breakpoint = Breakpoint::Pc { address, bank };
+                    gameboy.add_breakpoint(breakpoint);
+                    self.status = format!(
+                        "Breakpoint set at {}",
+                        format_breakpoint(gameboy.symbols(), &breakpoint)
+                    );
+                }
+                None => self.status = format!("Invalid location {}", location),
+            },
+            ("bi", None, _) => {
+                gameboy.add_breakpoint(Breakpoint::Interrupt(None));
+                self.status = String::from("Breaking on any interrupt");
+            }
+            ("bi", Some(interrupt), _) => match interrupt.parse::<u8>() {
+                Ok(interrupt) if interrupt < 5 => {
+                    gameboy.add_breakpoint(Breakpoint::Interrupt(Some(interrupt)));
+                    self.status = format!("Breaking on interrupt {}", interrupt);
+                }
+                _ => self.status = format!("Invalid interrupt {}, expected 0-4", interrupt),
+            },
+            ("bo", Some(opcode), _) => match parse_byte(opcode) {
+                Some(opcode) => {
+                    gameboy.add_breakpoint(Breakpoint::Opcode(opcode));
+                    self.status = format!("Breaking on opcode ${:02X}", opcode);
+                }
+                None => self.status = format!("Invalid opcode {}", opcode),
+            },
+            ("w" | "watch", Some(_), _) => {
+                match parse_watchpoint(gameboy.symbols(), command.split_whitespace().skip(1)) {
+                    Some(watchpoint) => {
+                        gameboy.add_watchpoint(watchpoint);
+                        self.status =
+                            format!("Watchpoint set at {}", format_watchpoint(&watchpoint));
+                    }
+                    None => self.status = format!("Invalid watchpoint \"{}\"", command),
+                }
+            }
+            ("d" | "delete", Some("all"), _) => {
+                gameboy.clear_breakpoints();
+                gameboy.clear_watchpoints();
+                self.status = String::from("Breakpoints and watchpoints cleared");
+            }
+            ("d" | "delete", _, Some(address)) => {
+                let breakpoints = gameboy.breakpoints().to_vec();
+                let watchpoints = gameboy.watchpoints().to_vec();
+                let mut deleted = 0;
+
+                for breakpoint in breakpoints {
+                    if matches!(breakpoint, Breakpoint::Pc { address: pc, .. } if pc == address) {
+                        deleted += gameboy.remove_breakpoint(breakpoint) as usize;
+                    }
+                }
+                for watchpoint in watchpoints {
+                    if watchpoint.start == address {
+                        deleted += gameboy.remove_watchpoint(watchpoint) as usize;
+                    }
+                }
+
+                self.status = if deleted > 0 {
+                    format!("Deleted {} at ${:04X}", deleted, address)
+                } else {
+                    format!("Nothing set at ${:04X}", address)
+                };
+            }
+            ("di", _, _) => {
+                for breakpoint in gameboy.breakpoints().to_vec() {
+                    if matches!(breakpoint, Breakpoint::Interrupt(_)) {
+                        gameboy.remove_breakpoint(breakpoint);
+                    }
+                }
+                self.status = String::from("Interrupt breakpoints deleted");
+            }
+            ("bs", _, _) => {
+                gameboy.add_breakpoint(Breakpoint::StackMismatch);
+                self.status = String::from("Breaking on call stack mismatches");
+            }
+            ("ds", _, _) => {
+                gameboy.remove_breakpoint(Breakpoint::StackMismatch);
+                self.status = String::from("Call stack breakpoint deleted");
+            }
+            ("do", Some(opcode), _) => match parse_byte(opcode) {
+                Some(opcode) => {
+                    gameboy.remove_breakpoint(Breakpoint::Opcode(opcode));
+                    self.status = format!("Opcode ${:02X} breakpoint deleted", opcode);
+                }
+                None => self.status = format!("Invalid opcode {}", opcode),
+            },
+            ("ch" | "cheat", None, _) => self.status = format_cheats(gameboy),
+            ("ch" | "cheat", Some("add"), _) => {
+                let mut rest = command.splitn(4, char::is_whitespace).skip(2);
+                let code = rest.next().unwrap_or("");
+                match Cheat::new(code, rest.next().unwrap_or("")) {
+                    Ok(cheat) => {
+                        self.status = format!("Added cheat {}", cheat.code);
+                        gameboy.add_cheat(cheat);
+                    }
+                    Err(err) => self.status = err.to_string(),
+                }
+            }
+            ("ch" | "cheat", Some(index), _) => match index.parse::<usize>() {
+                Ok(index) if index < gameboy.cheats().len() => {
+                    let enabled = !gameboy.cheats()[index].enabled;
+                    gameboy.set_cheat_enabled(index, enabled);
+                    self.status = format_cheats(gameboy);
+                }
+                _ => self.status = format!("No cheat {}", index),
+            },
+            ("rs", _, _) => match argument.unwrap_or("u8").parse::<ValueType>() {
+                Ok(value_type) => {
+                    let count = gameboy.start_ram_search(value_type);
+                    self.status = format!("RAM search started, {} candidates", count);
+                }
+                Err(err) => self.status = err.to_string(),
+            },
+            ("rf", Some(comparison), _) => {
+                let operand = match words.next() {
+                    None => Ok(Operand::Previous),
+                    Some(value) => parse_value(value)
+                        .map(Operand::Value)
+                        .ok_or_else(|| format!("Invalid value {}", value)),
+                };
+
+                self.status = match (comparison.parse::<Comparison>(), operand) {
+                    (Ok(comparison), Ok(operand)) => {
+                        match gameboy.filter_ram_search(comparison, operand) {
+                            Some(count) => format!("{} candidates left", count),
+                            None => String::from("Start a search with rs first"),
+                        }
+                    }
+                    (Err(err), _) => err.to_string(),
+                    (_, Err(err)) => err,
+                };
+            }
+            ("rc", _, _) => {
+                gameboy.clear_ram_search();
+                self.status = String::from("RAM search cleared");
+            }
+            ("m" | "memory", _, Some(address)) => self.memory_address = address & 0xFFF0,
+            ("q" | "quit", _, _) => return DebugAction::Quit,
+            ("bo" | "do" | "w" | "watch" | "rf", None, _) => {
+                self.status = format!("{} needs an argument", name)
+            }
+            ("r" | "run" | "b" | "break" | "d" | "delete" | "m" | "memory", _, None) => {
+                self.status = format!("{} needs a hex address", name)
+            }
+            _ => self.status = format!("Unknown command \"{}\", {}", command, HELP),
+        }
+
+        DebugAction::None
+    }
+
+    fn handle_events(&mut self, gameboy: &mut Gameboy) -> io::Result<DebugAction> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.stop(gameboy, "Paused")
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Esc => self.stop(gameboy, "Paused"),
+                KeyCode::PageUp => {
+                    self.memory_address = self.memory_address.wrapping_sub(MEMORY_ROWS * 16)
+                }
+                KeyCode::PageDown => {
+                    self.memory_address = self.memory_address.wrapping_add(MEMORY_ROWS * 16)
+                }
+                KeyCode::Enter => {
+                    let command = std::mem::take(&mut self.input);
+                    if self.execute(gameboy, &command) == DebugAction::Quit {
+                        return Ok(DebugAction::Quit);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(DebugAction::None)
+    }
+
+    //Handles typed commands and redraws, called once per host frame
+    pub fn update(&mut self, gameboy: &mut Gameboy) -> io::Result<DebugAction> {
+        let action = self.handle_events(gameboy)?;
+
+        if let Some(mut terminal) = self.terminal.take() {
+            let result = terminal.draw(|frame| self.draw(frame, gameboy)).map(|_| ());
+            self.terminal = Some(terminal);
+            result?;
+        }
+
+        Ok(action)
+    }
+
+    fn draw(&self, frame: &mut Frame, gameboy: &Gameboy) {
+        let [main, command] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(4)]).areas(frame.area());
+        let [left, disassembly, memory] = Layout::horizontal([
+            Constraint::Length(40),
+            Constraint::Length(36),
+            Constraint::Min(0),
+        ])
+        .areas(main);
+        let [registers, io] =
+            Layout::vertical([Constraint::Length(12), Constraint::Min(0)]).areas(left);
+
+        self.draw_registers(frame, registers, gameboy);
+        self.draw_io(frame, io, gameboy);
+        let [disassembly, call_stack] = Layout::vertical([
+            Constraint::Length(DISASSEMBLY_LINES as u16 + 2),
+            Constraint::Min(0),
+        ])
+        .areas(disassembly);
+        self.draw_disassembly(frame, disassembly, gameboy);
+        self.draw_call_stack(frame, call_stack, gameboy);
+        if gameboy.ram_search().is_some() {
+            let [memory, search] =
+                Layout::vertical([Constraint::Length(MEMORY_ROWS + 2), Constraint::Min(0)])
+                    .areas(memory);
+            self.draw_memory(frame, memory, gameboy);
+            self.draw_search(frame, search, gameboy);
+        } else {
+            self.draw_memory(frame, memory, gameboy);
+        }
+        self.draw_command(frame, command);
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect, gameboy: &Gameboy) {
+        let cpu = gameboy.cpu.borrow();
+        let interrupt = gameboy.interrupt.borrow();
+
+        let breakpoints = gameboy
+            .breakpoints()
+            .iter()
+            .map(|breakpoint| format_breakpoint(gameboy.symbols(), breakpoint))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let watchpoints = gameboy
+            .watchpoints()
+            .iter()
+            .map(format_watchpoint)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let lines = vec![
+            Line::from(format!("AF {:04X}   BC {:04X}", cpu.af(), cpu.bc())),
+            Line::from(format!("DE {:04X}   HL {:04X}", cpu.de(), cpu.hl())),
+            Line::from(format!("SP {:04X}   PC {:04X}", cpu.sp, cpu.pc)),
+            Line::from(vec![
+                flag("Z", cpu.z()),
+                flag("N", cpu.n()),
+                flag("H", cpu.h()),
+                flag("C", cpu.c()),
+            ]),
+            Line::from(vec![
+                flag("IME", interrupt.ime),
+                flag("HALT", cpu.halt),
+                flag("LOCKED", cpu.locked),
+            ]),
+            Line::from(format!(
+                "Bank ROM {:X} SRAM {:X}",
+                gameboy.bus.rom_bank(),
+                gameboy.bus.ram_bank()
+            )),
+            Line::from(""),
+            Line::from(format!("Breakpoints: {}", breakpoints)),
+            Line::from(format!("Watchpoints: {}", watchpoints)),
+        ];
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("CPU")),
+            area,
+        );
+    }
+
+    fn draw_io(&self, frame: &mut Frame, area: Rect, gameboy: &Gameboy) {
+        let bus = &gameboy.bus;
+        let lcdc = bus.peek_byte(LCDC);
+        let stat = bus.peek_byte(STAT);
+        let tac = bus.peek_byte(TAC);
+
+        let clock = match tac & 0b11 {
+            0 => "4096Hz",
+            1 => "262144Hz",
+            2 => "65536Hz",
+            _ => "16384Hz",
+        };
+
+        let lines = vec![
+            Line::from(format!("LCDC {:02X}", lcdc)),
+            Line::from(vec![
+                flag("LCD", bit(lcdc, 7)),
+                flag("WIN", bit(lcdc, 5)),
+                flag("OBJ", bit(lcdc, 1)),
+                flag("BG", bit(lcdc, 0)),
+            ]),
+            Line::from(format!(
+                "BG map {} Window map {}",
+                if bit(lcdc, 3) { "9C00" } else { "9800" },
+                if bit(lcdc, 6) { "9C00" } else { "9800" },
+            )),
+            Line::from(format!(
+                "Tiles {} OBJ 8x{}",
+                if bit(lcdc, 4) { "8000" } else { "8800" },
+                if bit(lcdc, 2) { 16 } else { 8 },
+            )),
+            Line::from(format!(
+                "STAT {:02X} mode {} LY {:02X} LYC {:02X}",
+                stat,
+                stat & 0b11,
+                bus.peek_byte(LY),
+                bus.peek_byte(LYC)
+            )),
+            Line::from(vec![
+                flag("LYC=LY", bit(stat, 2)),
+                flag("HBL", bit(stat, 3)),
+                flag("VBL", bit(stat, 4)),
+                flag("OAM", bit(stat, 5)),
+                flag("LYC", bit(stat, 6)),
+            ]),
+            Line::from(format!(
+                "TAC {:02X} {} {}",
+                tac,
+                if bit(tac, 2) { "on" } else { "off" },
+                clock
+            )),
+            Line::from(format!(
+                "DIV {:02X} TIMA {:02X} TMA {:02X}",
+                bus.peek_byte(DIV),
+                bus.peek_byte(TIMA),
+                bus.peek_byte(TMA)
+            )),
+            Line::from(
+                [
+                    vec![Span::raw("IE ")],
+                    interrupt_flags(bus.peek_byte(ADDRESS_IE)),
+                ]
+                .concat(),
+            ),
+            Line::from(
+                [
+                    vec![Span::raw("IF ")],
+                    interrupt_flags(bus.peek_byte(ADDRESS_IF)),
+                ]
+                .concat(),
+            ),
+        ];
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("IO")),
+            area,
+        );
+    }
+
+    fn draw_disassembly(&self, frame: &mut Frame, area: Rect, gameboy: &Gameboy) {
+        let symbols = gameboy.symbols();
+        let pc = gameboy.cpu.borrow().pc;
+        let mut address = pc;
+        let mut lines = Vec::with_capacity(DISASSEMBLY_LINES);
+
+        while lines.len() < DISASSEMBLY_LINES {
+            if let Some(label) = symbols.label(Location::at(&gameboy.bus, address)) {
+                lines.push(Line::styled(
+                    format!("{}:", label),
+                    Style::new().fg(Color::Green),
+                ));
+                if lines.len() == DISASSEMBLY_LINES {
+                    break;
+                }
+            }
+
+            let (mut instruction, length) = Cpu::disassemble_at(&gameboy.bus, address);
+            let target = branch_target(&gameboy.bus, address)
+                .and_then(|target| symbols.name(Location::at(&gameboy.bus, target)));
+            if let Some(target) = target {
+                instruction = format!("{} ; {}", instruction, target);
+            }
+
+            let breakpoint = has_breakpoint(gameboy, address);
+            let marker = match (address == pc, breakpoint) {
+                (true, _) => ">",
+                (false, true) => "*",
+                _ => " ",
+            };
+            let style = if address == pc {
+                Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else if breakpoint {
+                Style::new().fg(Color::Red)
+            } else {
+                Style::new()
+            };
+
+            lines.push(Line::styled(
+                format!(
+                    "{}{}:{:04X} {}",
+                    marker,
+                    region(gameboy, address),
+                    address,
+                    instruction
+                ),
+                style,
+            ));
+            address = address.wrapping_add(length);
+        }
+
+        //Where PC is, when there are symbols to tell
+        let location = Location::at(&gameboy.bus, pc);
+        let title = match (symbols.name(location), symbols.section(location)) {
+            (Some(name), Some(section)) if !name.starts_with(&section.name) => {
+                format!("Disassembly {} [{}]", name, section.name)
+            }
+            (Some(name), _) => format!("Disassembly {}", name),
+            _ => String::from("Disassembly"),
+        };
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    //Innermost call first, with where each one returns to
+    fn draw_call_stack(&self, frame: &mut Frame, area: Rect, gameboy: &Gameboy) {
+        let call_stack = gameboy.call_stack();
+
+        let rows = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = call_stack
+            .iter()
+            .rev()
+            .enumerate()
+            .take(rows)
+            .map(|(depth, call)| {
+                Line::from(format!("#{:<2}{}", depth, call.describe(gameboy.symbols())))
+            })
+            .collect();
+
+        let title = format!("Call stack ({})", call_stack.len());
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect, gameboy: &Gameboy) {
+        let lines: Vec<Line> = (0..MEMORY_ROWS)
+            .map(|row| {
+                let start = self.memory_address.wrapping_add(row * 16);
+                let bytes: Vec<u8> = (0..16)
+                    .map(|offset| gameboy.bus.peek_byte(start.wrapping_add(offset)))
+                    .collect();
+
+                let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                let ascii: String = bytes
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+
+                Line::from(format!(
+                    "{:>6}:{:04X} {} {}",
+                    region(gameboy, start),
+                    start,
+                    hex.join(" "),
+                    ascii
+                ))
+            })
+            .collect();
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Memory (PgUp/PgDn)")),
+            area,
+        );
+    }
+
+    fn draw_search(&self, frame: &mut Frame, area: Rect, gameboy: &Gameboy) {
+        let Some(search) = gameboy.ram_search() else {
+            return;
+        };
+
+        let rows = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = search
+            .candidates()
+            .iter()
+            .take(rows)
+            .map(|candidate| {
+                let current = search
+                    .current(&gameboy.bus, candidate.address)
+                    .map_or(String::from("-"), |value| value.to_string());
+
+                Line::from(format!(
+                    "{:>6}:{:04X} {:>6} (was {})",
+                    region(gameboy, candidate.address),
+                    candidate.address,
+                    current,
+                    candidate.previous
+                ))
+            })
+            .collect();
+
+        let title = format!(
+            "RAM search ({:?}, {} candidates)",
+            search.value_type,
+            search.candidates().len()
+        );
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_command(&self, frame: &mut Frame, area: Rect) {
+        let state = if self.running { "RUN" } else { "STOP" };
+
+        let lines = vec![
+            Line::from(self.status.as_str()),
+            Line::from(vec![
+                Span::styled(format!("[{}] > ", state), Style::new().fg(Color::Cyan)),
+                Span::raw(self.input.as_str()),
+            ]),
+        ];
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Command")),
+            area,
+        );
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Filter {
+    #[default]
+    None,
+    Scale2x,
+    //Scale2x applied twice
+    Scale4x,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overlay {
+    #[default]
+    None,
+    Grid,
+    Scanlines,
+}
+
+//How much darker the overlay lines are, out of 256
+const GRID_DARKEN: u32 = 64;
+const SCANLINE_DARKEN: u32 = 96;
+
+//Mixes two 0x00RRGGBB colors, weight is how much of b is kept (0-256)
+fn mix(a: u32, b: u32, weight: u32) -> u32 {
+    let channel = |shift: u32| {
+        let a = (a >> shift) & 0xFF;
+        let b = (b >> shift) & 0xFF;
+        ((a * (256 - weight) + b * weight) >> 8) << shift
+    };
+
+    channel(16) | channel(8) | channel(0)
+}
+
+fn darken(color: u32, amount: u32) -> u32 {
+    mix(color, 0, amount)
+}
+
+//EPX/Scale2x, doubles the resolution while keeping edges sharp
+pub fn scale2x(pixels: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let mut out = vec![0u32; pixels.len() * 4];
+    let at = |x: usize, y: usize| pixels[y * width + x];
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = at(x, y);
+            let a = at(x, y.saturating_sub(1));
+            let b = at((x + 1).min(width - 1), y);
+            let c = at(x.saturating_sub(1), y);
+            let d = at(x, (y + 1).min(height - 1));
+
+            let top = (y * 2) * width * 2 + x * 2;
+            let bottom = top + width * 2;
+
+            out[top] = if c == a && c != d && a != b { a } else { p };
+            out[top + 1] = if a == b && a != c && b != d { b } else { p };
+            out[bottom] = if d == c && d != b && c != a { c } else { p };
+            out[bottom + 1] = if b == d && b != a && d != c { d } else { p };
+        }
+    }
+
+    out
+}
+
+fn scale_nearest(pixels: &[u32], width: usize, height: usize, factor: usize) -> Vec<u32> {
+    if factor == 1 {
+        return pixels.to_vec();
+    }
+
+    let mut out = Vec::with_capacity(pixels.len() * factor * factor);
+    for row in pixels.chunks(width).take(height) {
+        let start = out.len();
+        for &pixel in row {
+            out.extend(std::iter::repeat_n(pixel, factor));
+        }
+
+        for _ in 1..factor {
+            out.extend_from_within(start..start + width * factor);
+        }
+    }
+
+    out
+}
+
+//CPU side post processing, run on every presented frame
+pub struct LcdEffects {
+    pub filter: Filter,
+    pub overlay: Overlay,
+    //0 disables frame blending, 1 never lets go of the previous frame
+    pub ghosting: f32,
+
+    previous: Vec<u32>,
+}
+
+impl LcdEffects {
+    pub fn new(filter: Filter, overlay: Overlay, ghosting: f32) -> Self {
+        Self {
+            filter,
+            overlay,
+            ghosting: ghosting.clamp(0.0, 1.0),
+            previous: Vec::new(),
+        }
+    }
+
+    //Blends the frame with what was shown before, like the slow DMG LCD does
+    fn blend(&mut self, frame: &[u32]) -> Vec<u32> {
+        let weight = (self.ghosting * 256.0) as u32;
+        if weight == 0 || self.previous.len() != frame.len() {
+            self.previous = frame.to_vec();
+            return self.previous.clone();
+        }
+
+        for (previous, &pixel) in self.previous.iter_mut().zip(frame) {
+            *previous = mix(pixel, *previous, weight);
+        }
+
+        self.previous.clone()
+    }
+
+    fn draw_overlay(&self, pixels: &mut [u32], width: usize, cell: usize) {
+        let (lines, amount) = match self.overlay {
+            Overlay::None => return,
+            Overlay::Grid if cell >= 3 => (true, GRID_DARKEN),
+            Overlay::Scanlines if cell >= 2 => (false, SCANLINE_DARKEN),
+            _ => return,
+        };
+
+        for (y, row) in pixels.chunks_mut(width).enumerate() {
+            let is_row_edge = y % cell == cell - 1;
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                if is_row_edge || (lines && x % cell == cell - 1) {
+                    *pixel = darken(*pixel, amount);
+                }
+            }
+        }
+    }
+
+    //Returns the processed frame and its size, scaled by at most `scale`
+    pub fn process(
+        &mut self,
+        frame: &[u32],
+        width: usize,
+        height: usize,
+        scale: usize,
+    ) -> (Vec<u32>, usize, usize) {
+        let mut pixels = self.blend(frame);
+        let (mut width, mut height) = (width, height);
+
+        let passes = match self.filter {
+            Filter::None => 0,
+            Filter::Scale2x => 1,
+            Filter::Scale4x => 2,
+        };
+
+        let mut factor = 1;
+        for _ in 0..passes {
+            if factor * 2 > scale {
+                break;
+            }
+
+            pixels = scale2x(&pixels, width, height);
+            width *= 2;
+            height *= 2;
+            factor *= 2;
+        }
+
+        let nearest = (scale / factor).max(1);
+        pixels = scale_nearest(&pixels, width, height, nearest);
+        width *= nearest;
+        height *= nearest;
+
+        self.draw_overlay(&mut pixels, width, factor * nearest);
+
+        (pixels, width, height)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    panic::{self, AssertUnwindSafe},
+};
+
+use log::{error, info};
+
+use crate::emulator::{
+    gameboy::{Gameboy, CYCLES_PER_FRAME},
+    movie::MovieMode,
+};
+
+use super::image::write_image;
+
+//LD B,B, used by test roms (mooneye, ...) as a software breakpoint
+const SOFTWARE_BREAKPOINT: u8 = 0x40;
+
+#[derive(Debug, Default, Clone)]
+pub struct HeadlessOptions {
+    pub frames: Option<u64>,
+    pub until_pc: Option<u16>,
+    pub until_breakpoint: bool,
+
+    pub output: Option<String>,
+    pub hash_log: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    FrameLimit,
+    ReachedPc,
+    Breakpoint,
+    LockUp,
+    Panic,
+    //Read-only movie playback ran out of frames
+    MovieEnd,
+    MovieDesync,
+}
+
+impl ExitReason {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ExitReason::FrameLimit
+            | ExitReason::ReachedPc
+            | ExitReason::Breakpoint
+            | ExitReason::MovieEnd => 0,
+            ExitReason::LockUp => 2,
+            ExitReason::Panic => 3,
+            ExitReason::MovieDesync => 4,
+        }
+    }
+}
+
+fn should_stop(gameboy: &Gameboy, options: &HeadlessOptions) -> Option<ExitReason> {
+    let pc = gameboy.cpu.borrow().pc;
+
+    if options.until_pc == Some(pc) {
+        return Some(ExitReason::ReachedPc);
+    }
+
+    if options.until_breakpoint && gameboy.bus.peek_byte(pc) == SOFTWARE_BREAKPOINT {
+        return Some(ExitReason::Breakpoint);
+    }
+
+    if gameboy.is_locked_up() {
+        return Some(ExitReason::LockUp);
+    }
+
+    None
+}
+
+fn run_frames(
+    gameboy: &mut Gameboy,
+    options: &HeadlessOptions,
+    hash_log: &mut Option<BufWriter<File>>,
+    frame: &mut u64,
+) -> io::Result<ExitReason> {
+    loop {
+        if options.frames.is_some_and(|frames| *frame >= frames) {
+            return Ok(ExitReason::FrameLimit);
+        }
+
+        let target = gameboy.accum_cycle + CYCLES_PER_FRAME;
+        while gameboy.accum_cycle < target {
+            if let Some(reason) = should_stop(gameboy, options) {
+                return Ok(reason);
+            }
+
+            gameboy.tick();
+        }
+
+        *frame += 1;
+
+        if let Some(log) = hash_log {
+            writeln!(log, "{} {:016x}", frame, gameboy.frame_hash())?;
+        }
+
+        if let Some(status) = gameboy.movie_status() {
+            if let Some(desync) = status.desync {
+                error!(target: "Headless", "Movie desynced at frame {}", desync);
+                return Ok(ExitReason::MovieDesync);
+            }
+            if status.mode == MovieMode::Finished {
+                return Ok(ExitReason::MovieEnd);
+            }
+        }
+    }
+}
+
+//Where the game came from when it crashed, innermost call first
+fn log_call_stack(gameboy: &Gameboy) {
+    let call_stack = gameboy.call_stack();
+    error!(target: "Headless", "Call stack ({} calls deep):", call_stack.len());
+
+    for (depth, call) in call_stack.iter().rev().enumerate() {
+        error!(target: "Headless", "  #{} {}", depth, call.describe(gameboy.symbols()));
+    }
+}
+
+//Runs without a window until a stop condition, then dumps the last frame
+pub fn run(gameboy: &mut Gameboy, options: &HeadlessOptions) -> io::Result<ExitReason> {
+    let mut hash_log = match &options.hash_log {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+
+    let mut frame = 0u64;
+
+    let reason = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_frames(gameboy, options, &mut hash_log, &mut frame)
+    }))
+    .unwrap_or_else(|_| {
+        error!(target: "Headless", "Emulator panicked at frame {}", frame);
+        Ok(ExitReason::Panic)
+    })?;
+
+    info!(
+        target: "Headless",
+        "Stopped after {} frames ({:?}) at PC={:04X}",
+        frame,
+        reason,
+        gameboy.cpu.borrow().pc
+    );
+
+    if matches!(reason, ExitReason::LockUp | ExitReason::Panic) {
+        log_call_stack(gameboy);
+    }
+
+    if let Some(log) = &mut hash_log {
+        log.flush()?;
+    }
+
+    if let Some(path) = &options.output {
+        write_image(path, &gameboy.get_rgb_frame_buffer(), 160, 144)?;
+    }
+
+    Ok(reason)
+}
@@ -0,0 +1,80 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::emulator::png::write_png;
+
+//Binary PPM (P6), pixels are 0x00RRGGBB
+pub fn write_ppm(path: &str, pixels: &[u32], width: usize, height: usize) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    for &pixel in pixels {
+        file.write_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])?;
+    }
+
+    file.flush()
+}
+
+//PPM when the path asks for it, PNG otherwise
+pub fn write_image(path: &str, pixels: &[u32], width: usize, height: usize) -> io::Result<()> {
+    if path.to_ascii_lowercase().ends_with(".ppm") {
+        write_ppm(path, pixels, width, height)
+    } else {
+        write_png(path, pixels, width, height)
+    }
+}
+
+//UTC time as YYYYMMDD-HHMMSS, without pulling in a date crate
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let (days, time) = ((secs / 86400) as i64, secs % 86400);
+
+    //Days to civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        (time / 60) % 60,
+        time % 60
+    )
+}
+
+//e.g. screenshots/TETRIS-20250101-120000.png, with a counter if taken within the same second
+pub fn screenshot_path(dir: &str, name: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let stem = format!("{}-{}", name, timestamp());
+
+    let mut path = Path::new(dir).join(format!("{}.png", stem));
+    let mut count = 1;
+    while path.exists() {
+        path = Path::new(dir).join(format!("{}-{}.png", stem, count));
+        count += 1;
+    }
+
+    Ok(path)
+}
@@ -0,0 +1,322 @@
+use std::io::{self, ErrorKind};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::emulator::joypad::Joypad;
+
+use super::config::Config;
+
+const KEYS: [Key; 106] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::F13,
+    Key::F14,
+    Key::F15,
+    Key::Down,
+    Key::Left,
+    Key::Right,
+    Key::Up,
+    Key::Apostrophe,
+    Key::Backquote,
+    Key::Backslash,
+    Key::Comma,
+    Key::Equal,
+    Key::LeftBracket,
+    Key::Minus,
+    Key::Period,
+    Key::RightBracket,
+    Key::Semicolon,
+    Key::Slash,
+    Key::Backspace,
+    Key::Delete,
+    Key::End,
+    Key::Enter,
+    Key::Escape,
+    Key::Home,
+    Key::Insert,
+    Key::Menu,
+    Key::PageDown,
+    Key::PageUp,
+    Key::Pause,
+    Key::Space,
+    Key::Tab,
+    Key::NumLock,
+    Key::CapsLock,
+    Key::ScrollLock,
+    Key::LeftShift,
+    Key::RightShift,
+    Key::LeftCtrl,
+    Key::RightCtrl,
+    Key::NumPad0,
+    Key::NumPad1,
+    Key::NumPad2,
+    Key::NumPad3,
+    Key::NumPad4,
+    Key::NumPad5,
+    Key::NumPad6,
+    Key::NumPad7,
+    Key::NumPad8,
+    Key::NumPad9,
+    Key::NumPadDot,
+    Key::NumPadSlash,
+    Key::NumPadAsterisk,
+    Key::NumPadMinus,
+    Key::NumPadPlus,
+    Key::NumPadEnter,
+    Key::LeftAlt,
+    Key::RightAlt,
+    Key::LeftSuper,
+    Key::RightSuper,
+];
+
+//Key names are minifb's variant names, case insensitive ("Z", "Key1", "LeftShift", ...)
+pub fn parse_key(name: &str) -> Option<Key> {
+    KEYS.iter()
+        .copied()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+fn parse_keys(action: &str, names: &[String]) -> io::Result<Vec<Key>> {
+    names
+        .iter()
+        .map(|name| {
+            parse_key(name).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unknown key \"{}\" bound to {}", name, action),
+                )
+            })
+        })
+        .collect()
+}
+
+//The n-th key picks save state slot n
+fn parse_slot_keys(names: &[String]) -> io::Result<Vec<(Hotkey, Vec<Key>)>> {
+    if names.len() > STATE_SLOTS {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} keys bound to select_slot, there are only {} slots",
+                names.len(),
+                STATE_SLOTS
+            ),
+        ));
+    }
+
+    names
+        .iter()
+        .enumerate()
+        .map(|(slot, name)| {
+            let keys = parse_keys("select_slot", std::slice::from_ref(name))?;
+            Ok((Hotkey::SelectSlot(slot as u8), keys))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Start,
+    Select,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Quit,
+    Pause,
+    Reset,
+    FastForward,
+    Screenshot,
+    SaveState,
+    LoadState,
+    SelectSlot(u8),
+    ReloadConfig,
+    NextPalette,
+    FrameAdvance,
+    Turbo,
+    SlowMotion,
+    ToggleBg,
+    ToggleWindow,
+    ToggleSprites,
+    TintLayers,
+    ToggleCheats,
+    Rewind,
+}
+
+pub const STATE_SLOTS: usize = 10;
+
+pub struct Input {
+    buttons: Vec<(Button, Vec<Key>)>,
+    hotkeys: Vec<(Hotkey, Vec<Key>)>,
+}
+
+impl Input {
+    pub fn new(config: &Config) -> io::Result<Self> {
+        let joypad = &config.joypad;
+        let hotkeys = &config.hotkeys;
+
+        let mut input = Self {
+            buttons: vec![
+                (Button::A, parse_keys("a", &joypad.a)?),
+                (Button::B, parse_keys("b", &joypad.b)?),
+                (Button::Start, parse_keys("start", &joypad.start)?),
+                (Button::Select, parse_keys("select", &joypad.select)?),
+                (Button::Up, parse_keys("up", &joypad.up)?),
+                (Button::Down, parse_keys("down", &joypad.down)?),
+                (Button::Left, parse_keys("left", &joypad.left)?),
+                (Button::Right, parse_keys("right", &joypad.right)?),
+            ],
+            hotkeys: vec![
+                (Hotkey::Quit, parse_keys("quit", &hotkeys.quit)?),
+                (Hotkey::Pause, parse_keys("pause", &hotkeys.pause)?),
+                (Hotkey::Reset, parse_keys("reset", &hotkeys.reset)?),
+                (
+                    Hotkey::FastForward,
+                    parse_keys("fast_forward", &hotkeys.fast_forward)?,
+                ),
+                (
+                    Hotkey::Screenshot,
+                    parse_keys("screenshot", &hotkeys.screenshot)?,
+                ),
+                (
+                    Hotkey::SaveState,
+                    parse_keys("save_state", &hotkeys.save_state)?,
+                ),
+                (
+                    Hotkey::LoadState,
+                    parse_keys("load_state", &hotkeys.load_state)?,
+                ),
+                (
+                    Hotkey::ReloadConfig,
+                    parse_keys("reload_config", &hotkeys.reload_config)?,
+                ),
+                (
+                    Hotkey::NextPalette,
+                    parse_keys("next_palette", &hotkeys.next_palette)?,
+                ),
+                (
+                    Hotkey::FrameAdvance,
+                    parse_keys("frame_advance", &hotkeys.frame_advance)?,
+                ),
+                (Hotkey::Turbo, parse_keys("turbo", &hotkeys.turbo)?),
+                (
+                    Hotkey::SlowMotion,
+                    parse_keys("slow_motion", &hotkeys.slow_motion)?,
+                ),
+                (
+                    Hotkey::ToggleBg,
+                    parse_keys("toggle_bg", &hotkeys.toggle_bg)?,
+                ),
+                (
+                    Hotkey::ToggleWindow,
+                    parse_keys("toggle_window", &hotkeys.toggle_window)?,
+                ),
+                (
+                    Hotkey::ToggleSprites,
+                    parse_keys("toggle_sprites", &hotkeys.toggle_sprites)?,
+                ),
+                (
+                    Hotkey::TintLayers,
+                    parse_keys("tint_layers", &hotkeys.tint_layers)?,
+                ),
+                (
+                    Hotkey::ToggleCheats,
+                    parse_keys("toggle_cheats", &hotkeys.toggle_cheats)?,
+                ),
+                (Hotkey::Rewind, parse_keys("rewind", &hotkeys.rewind)?),
+            ],
+        };
+
+        input.hotkeys.extend(parse_slot_keys(&hotkeys.select_slot)?);
+        Ok(input)
+    }
+
+    pub fn is_button_down(&self, window: &Window, button: Button) -> bool {
+        self.buttons
+            .iter()
+            .filter(|(bound, _)| *bound == button)
+            .any(|(_, keys)| keys.iter().any(|&key| window.is_key_down(key)))
+    }
+
+    //Joypad fields are active low
+    pub fn update_joypad(&self, window: &Window, joypad: &mut Joypad) {
+        joypad.btn_a = !self.is_button_down(window, Button::A);
+        joypad.btn_b = !self.is_button_down(window, Button::B);
+        joypad.start = !self.is_button_down(window, Button::Start);
+        joypad.select = !self.is_button_down(window, Button::Select);
+        joypad.up = !self.is_button_down(window, Button::Up);
+        joypad.down = !self.is_button_down(window, Button::Down);
+        joypad.left = !self.is_button_down(window, Button::Left);
+        joypad.right = !self.is_button_down(window, Button::Right);
+    }
+
+    pub fn is_hotkey_down(&self, window: &Window, hotkey: Hotkey) -> bool {
+        self.hotkeys
+            .iter()
+            .filter(|(bound, _)| *bound == hotkey)
+            .any(|(_, keys)| keys.iter().any(|&key| window.is_key_down(key)))
+    }
+
+    //Hotkeys that went down since the last window update
+    pub fn pressed_hotkeys(&self, window: &Window) -> Vec<Hotkey> {
+        self.hotkeys
+            .iter()
+            .filter(|(_, keys)| {
+                keys.iter()
+                    .any(|&key| window.is_key_pressed(key, KeyRepeat::No))
+            })
+            .map(|&(hotkey, _)| hotkey)
+            .collect()
+    }
+}
@@ -1 +1,12 @@
+pub mod config;
+pub mod debugger;
+pub mod effects;
+pub mod headless;
+pub mod image;
+pub mod input;
+pub mod osd;
+pub mod pacing;
+pub mod palette;
 pub mod screen;
+pub mod speed;
+pub mod viewer;
@@ -0,0 +1,229 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+use crate::emulator::joypad::Joypad;
+
+use super::config::OsdConfig;
+
+//3x5 glyphs for ' ' to '_', one bit per pixel, top left pixel is bit 14
+const FONT: [u16; 64] = [
+    0x0000, 0x2482, 0x5A00, 0x5F7D, 0x3C9E, 0x52A5, 0x2AAB, 0x2400, 0x1491, 0x4494, 0x0AA8, 0x05D0,
+    0x0014, 0x01C0, 0x0002, 0x12A4, 0x7B6F, 0x2C97, 0x73E7, 0x72CF, 0x5BC9, 0x79CF, 0x79EF, 0x7292,
+    0x7BEF, 0x7BCF, 0x0410, 0x0414, 0x1511, 0x0E38, 0x4454, 0x72C2, 0x2BE3, 0x2BED, 0x6BAE, 0x3923,
+    0x6B6E, 0x79A7, 0x79A4, 0x396B, 0x5BED, 0x7497, 0x126A, 0x5BAD, 0x4927, 0x5FED, 0x6B6D, 0x2B6A,
+    0x6BA4, 0x2B73, 0x6BAD, 0x388E, 0x7492, 0x5B6F, 0x5B6A, 0x5BFD, 0x5AAD, 0x5A92, 0x72A7, 0x3493,
+    0x4889, 0x6496, 0x2A00, 0x0007,
+];
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+//Glyph plus a column of spacing, and a row above and below
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+const TEXT_COLOR: u32 = 0xFFFFFF;
+//How much of the game shows through the text background, out of 256
+const BACKGROUND_ALPHA: u32 = 96;
+
+const MAX_MESSAGES: usize = 3;
+
+//Lowercase is drawn as uppercase, anything else outside the font as '?'
+fn glyph(c: char) -> u16 {
+    let c = c.to_ascii_uppercase();
+    match c {
+        ' '..='_' => FONT[c as usize - 0x20],
+        _ => FONT['?' as usize - 0x20],
+    }
+}
+
+fn shade(color: u32) -> u32 {
+    let channel = |shift: u32| ((((color >> shift) & 0xFF) * BACKGROUND_ALPHA) >> 8) << shift;
+    channel(16) | channel(8) | channel(0)
+}
+
+struct Canvas<'a> {
+    pixels: &'a mut [u32],
+    width: usize,
+    height: usize,
+    //Output pixels per font pixel
+    scale: usize,
+}
+
+impl Canvas<'_> {
+    fn text_width(&self, text: &str) -> usize {
+        (text.chars().count() * CELL_WIDTH + 1) * self.scale
+    }
+
+    fn fill(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        color: impl Fn(u32) -> u32,
+    ) {
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                let pixel = &mut self.pixels[row * self.width + column];
+                *pixel = color(*pixel);
+            }
+        }
+    }
+
+    //Draws text on a darkened box, (x, y) is the top left corner of the box
+    fn draw_text(&mut self, x: usize, y: usize, text: &str) {
+        let scale = self.scale;
+        self.fill(x, y, self.text_width(text), CELL_HEIGHT * scale, shade);
+
+        for (index, c) in text.chars().enumerate() {
+            let bits = glyph(c);
+            let left = x + (index * CELL_WIDTH + 1) * scale;
+
+            for row in 0..GLYPH_HEIGHT {
+                for column in 0..GLYPH_WIDTH {
+                    let bit = 14 - (row * GLYPH_WIDTH + column);
+                    if bits & (1 << bit) != 0 {
+                        self.fill(
+                            left + column * scale,
+                            y + (row + 1) * scale,
+                            scale,
+                            scale,
+                            |_| TEXT_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+//Unscaled text on a darkened box for other windows, (x, y) is the top left corner of the box
+pub(crate) fn draw_text(
+    pixels: &mut [u32],
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    text: &str,
+) {
+    let mut canvas = Canvas {
+        pixels,
+        width,
+        height,
+        scale: 1,
+    };
+    canvas.draw_text(x, y, text);
+}
+
+//Size of the box drawn by draw_text
+pub(crate) fn text_size(text: &str) -> (usize, usize) {
+    (text.chars().count() * CELL_WIDTH + 1, CELL_HEIGHT)
+}
+
+//Status overlay drawn over the presented frame
+pub struct Osd {
+    pub config: OsdConfig,
+
+    messages: VecDeque<(String, Instant)>,
+    frame: u64,
+    fps: u32,
+    speed: u64,
+    buttons: String,
+}
+
+impl Osd {
+    pub fn new(config: &OsdConfig) -> Self {
+        Self {
+            config: config.clone(),
+
+            messages: VecDeque::new(),
+            frame: 0,
+            fps: 0,
+            speed: 100,
+            buttons: String::new(),
+        }
+    }
+
+    //Also logged, so messages aren't lost with the OSD turned off
+    pub fn message(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        info!(target: "OSD", "{}", text);
+
+        let duration = Duration::from_secs_f32(self.config.message_duration.max(0.0));
+        self.messages.push_back((text, Instant::now() + duration));
+        while self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    pub fn set_speed(&mut self, fps: u32, speed: u64) {
+        self.fps = fps;
+        self.speed = speed;
+    }
+
+    //Joypad fields are active low
+    pub fn set_buttons(&mut self, joypad: &Joypad) {
+        let held = [
+            (joypad.up, "^"),
+            (joypad.down, "V"),
+            (joypad.left, "<"),
+            (joypad.right, ">"),
+            (joypad.btn_a, "A"),
+            (joypad.btn_b, "B"),
+            (joypad.select, "SEL"),
+            (joypad.start, "STA"),
+        ];
+
+        self.buttons = held
+            .iter()
+            .filter(|(released, _)| !released)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    pub fn draw(&mut self, pixels: &mut [u32], width: usize, height: usize, scale: usize) {
+        let now = Instant::now();
+        self.messages.retain(|(_, expiry)| *expiry > now);
+
+        let mut canvas = Canvas {
+            pixels,
+            width,
+            height,
+            scale: (scale / 2).max(1),
+        };
+        let line_height = CELL_HEIGHT * canvas.scale;
+
+        if self.config.frame_counter {
+            canvas.draw_text(0, 0, &self.frame.to_string());
+        }
+
+        if self.config.fps {
+            let text = format!("{} FPS {}%", self.fps, self.speed);
+            let x = width.saturating_sub(canvas.text_width(&text));
+            canvas.draw_text(x, 0, &text);
+        }
+
+        let mut bottom = height;
+        if self.config.input && !self.buttons.is_empty() {
+            bottom = bottom.saturating_sub(line_height);
+            let x = width.saturating_sub(canvas.text_width(&self.buttons));
+            canvas.draw_text(x, bottom, &self.buttons);
+        }
+
+        if self.config.messages {
+            for (text, _) in self.messages.iter().rev() {
+                bottom = bottom.saturating_sub(line_height);
+                canvas.draw_text(0, bottom, text);
+            }
+        }
+    }
+}
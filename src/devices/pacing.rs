@@ -0,0 +1,71 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::emulator::gameboy::{CLOCK_SPEED, CYCLES_PER_FRAME};
+
+//Sleeping is only accurate to a millisecond or so, the rest is spent spinning
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+//Falling further behind than this (debugger, window drag, ...) starts over instead of catching up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+//Paces host frames against emulated time, one frame is 70224 / 4194304 s (~59.7275 Hz)
+pub struct FramePacer {
+    start: Instant,
+    //Emulated time since start, in T-cycles
+    cycles: u128,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            cycles: 0,
+        }
+    }
+
+    //Forgets the schedule, used after fast forwarding or a long stall
+    pub fn resync(&mut self) {
+        self.start = Instant::now();
+        self.cycles = 0;
+    }
+
+    fn deadline(&self) -> Instant {
+        let nanos = self.cycles * 1_000_000_000 / CLOCK_SPEED;
+        self.start + Duration::from_nanos(nanos as u64)
+    }
+
+    //Waits until `cycles` more emulated T-cycles have passed on the host clock
+    pub fn wait(&mut self, cycles: u128) {
+        self.cycles += cycles;
+        let deadline = self.deadline();
+
+        let now = Instant::now();
+        if now > deadline + MAX_LAG {
+            self.resync();
+            return;
+        }
+
+        if let Some(remaining) = deadline.checked_duration_since(now) {
+            if remaining > SPIN_THRESHOLD {
+                thread::sleep(remaining - SPIN_THRESHOLD);
+            }
+        }
+
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    pub fn wait_frame(&mut self) {
+        self.wait(CYCLES_PER_FRAME);
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::{self, ErrorKind};
+
+use crate::emulator::colorization::{ColorPalette, GRAYSCALE};
+
+use super::config::{Config, PaletteConfig};
+
+const fn uniform(colors: [u32; 4]) -> ColorPalette {
+    ColorPalette {
+        bg: colors,
+        obj0: colors,
+        obj1: colors,
+    }
+}
+
+pub const BUILTIN_PALETTES: [(&str, ColorPalette); 6] = [
+    ("grayscale", GRAYSCALE),
+    ("dmg", uniform([0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F])),
+    ("pocket", uniform([0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F])),
+    ("light", uniform([0x00B581, 0x009A71, 0x00694A, 0x004F3B])),
+    (
+        "high-contrast",
+        uniform([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]),
+    ),
+    (
+        "inverted",
+        uniform([0x000000, 0x4A4A4A, 0x979797, 0xFFFFFF]),
+    ),
+];
+
+//Accepts "#RRGGBB", "0xRRGGBB" or "RRGGBB"
+pub fn parse_color(value: &str) -> Option<u32> {
+    let digits = value
+        .trim_start_matches('#')
+        .trim_start_matches("0x")
+        .trim_start_matches("0X");
+
+    if digits.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(digits, 16).ok()
+}
+
+fn parse_colors(name: &str, colors: &[String; 4]) -> io::Result<[u32; 4]> {
+    let mut parsed = [0u32; 4];
+
+    for (color, value) in parsed.iter_mut().zip(colors.iter()) {
+        *color = parse_color(value).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid color \"{}\" in palette {}", value, name),
+            )
+        })?;
+    }
+
+    Ok(parsed)
+}
+
+//OBJ palettes fall back to the BG colors when not given
+fn parse_palette(name: &str, palette: &PaletteConfig) -> io::Result<ColorPalette> {
+    let bg = parse_colors(name, &palette.bg)?;
+
+    Ok(ColorPalette {
+        bg,
+        obj0: match &palette.obj0 {
+            Some(colors) => parse_colors(name, colors)?,
+            None => bg,
+        },
+        obj1: match &palette.obj1 {
+            Some(colors) => parse_colors(name, colors)?,
+            None => bg,
+        },
+    })
+}
+
+pub struct DisplayPalettes {
+    palettes: Vec<(String, ColorPalette)>,
+    current: usize,
+}
+
+impl DisplayPalettes {
+    pub fn new(config: &Config) -> io::Result<Self> {
+        let mut palettes: Vec<(String, ColorPalette)> = BUILTIN_PALETTES
+            .iter()
+            .map(|&(name, palette)| (name.to_string(), palette))
+            .collect();
+
+        for (name, palette) in &config.palettes {
+            let palette = parse_palette(name, palette)?;
+
+            match palettes.iter_mut().find(|(existing, _)| existing == name) {
+                Some(existing) => existing.1 = palette,
+                None => palettes.push((name.clone(), palette)),
+            }
+        }
+
+        let mut display_palettes = Self {
+            palettes,
+            current: 0,
+        };
+        display_palettes.select(&config.palette)?;
+
+        Ok(display_palettes)
+    }
+
+    pub fn select(&mut self, name: &str) -> io::Result<()> {
+        self.current = self
+            .palettes
+            .iter()
+            .position(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown palette \"{}\", expected one of: {}",
+                        name,
+                        self.names().join(", ")
+                    ),
+                )
+            })?;
+
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.palettes
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn current(&self) -> (&str, ColorPalette) {
+        let (name, palette) = &self.palettes[self.current];
+        (name, *palette)
+    }
+
+    pub fn cycle(&mut self) -> (&str, ColorPalette) {
+        self.current = (self.current + 1) % self.palettes.len();
+        self.current()
+    }
+}
@@ -1,51 +1,86 @@
 use minifb::{Window, WindowOptions};
+use serde::{Deserialize, Serialize};
+
+use super::{config::DisplayConfig, effects::LcdEffects, osd::Osd};
 
 const WIDTH: usize = 160;
 const HEIGHT: usize = 144;
 
+const TITLE: &str = "Gameboy Emulator";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaleMode {
+    //Largest whole multiple that fits, centered with black borders
+    #[default]
+    Integer,
+    //Fills the window while keeping the 10:9 aspect ratio
+    Aspect,
+    Stretch,
+}
+
+impl ScaleMode {
+    fn to_minifb(self) -> minifb::ScaleMode {
+        match self {
+            ScaleMode::Integer => minifb::ScaleMode::Center,
+            ScaleMode::Aspect => minifb::ScaleMode::AspectRatioStretch,
+            ScaleMode::Stretch => minifb::ScaleMode::Stretch,
+        }
+    }
+}
+
 pub struct Screen {
     pub window: Window,
+    pub effects: LcdEffects,
 }
 
 impl Default for Screen {
     fn default() -> Self {
-        Self::new()
+        Self::new(&DisplayConfig::default())
     }
 }
 
 impl Screen {
-    pub fn new() -> Self {
+    pub fn new(display: &DisplayConfig) -> Self {
+        let scale = display.scale.max(1);
+
         Self {
             window: Window::new(
-                "Gameboy Emulator",
-                WIDTH,
-                HEIGHT,
+                TITLE,
+                WIDTH * scale,
+                HEIGHT * scale,
                 WindowOptions {
-                    scale: minifb::Scale::X4,
+                    resize: true,
+                    scale_mode: display.scale_mode.to_minifb(),
                     ..WindowOptions::default()
                 },
             )
             .unwrap(),
+            effects: LcdEffects::new(display.filter, display.overlay, display.ghosting),
         }
     }
 
-    pub fn render(&mut self, buffer: [u8; 160 * 144]) {
-        let mut frame_buffer: Vec<u32> = Vec::new();
+    //Effects are drawn at the largest whole scale that fits the window, minifb does the rest
+    fn fit_scale(&self) -> usize {
+        let (width, height) = self.window.get_size();
+        (width / WIDTH).min(height / HEIGHT).max(1)
+    }
 
-        for pixel in buffer {
-            let pixel = match pixel {
-                3 => 0xFF000000,
-                2 => 0xFF686868,
-                1 => 0xFFB5B5B5,
-                0 => 0xFFFFFFFF,
-                _ => panic!("Invalid pixel"),
-            };
+    pub fn set_status(&mut self, status: &str) {
+        self.window.set_title(&format!("{} - {}", TITLE, status));
+    }
 
-            frame_buffer.push(pixel);
-        }
+    pub fn apply_config(&mut self, display: &DisplayConfig) {
+        self.effects = LcdEffects::new(display.filter, display.overlay, display.ghosting);
+    }
+
+    pub fn render(&mut self, frame_buffer: &[u32], osd: &mut Osd) {
+        let scale = self.fit_scale();
+        let (mut pixels, width, height) = self.effects.process(frame_buffer, WIDTH, HEIGHT, scale);
+        osd.draw(&mut pixels, width, height, width / WIDTH);
 
         self.window
-            .update_with_buffer(&frame_buffer, WIDTH, HEIGHT)
+            .update_with_buffer(&pixels, width, height)
             .unwrap();
     }
 }
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+//Speeds cycled through by the slow motion hotkey
+const SLOW_MOTION_SPEEDS: [f64; 3] = [1.0, 0.5, 0.25];
+
+//Host time spent emulating per presented frame while fast forwarding
+pub const FAST_FORWARD_BUDGET: Duration = Duration::from_millis(16);
+
+//How many emulated frames to run per host frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameBudget {
+    Frames(u32),
+    //As many as fit in FAST_FORWARD_BUDGET
+    Unlimited,
+}
+
+pub struct SpeedControl {
+    pub paused: bool,
+    pub turbo: bool,
+    pub turbo_speed: f64,
+    //Held, not toggled
+    pub fast_forward: bool,
+
+    speed: f64,
+    frame_advance: bool,
+    //Fraction of a frame carried over, for speeds that aren't whole numbers
+    pending: f64,
+}
+
+impl SpeedControl {
+    pub fn new(speed: f64, turbo_speed: f64, paused: bool) -> Self {
+        Self {
+            paused,
+            turbo: false,
+            turbo_speed: turbo_speed.max(0.0),
+            fast_forward: false,
+
+            speed: speed.max(0.0),
+            frame_advance: false,
+            pending: 0.0,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn toggle_turbo(&mut self) {
+        self.turbo = !self.turbo;
+    }
+
+    //Pauses if needed and lets exactly one frame through
+    pub fn advance_frame(&mut self) {
+        self.paused = true;
+        self.frame_advance = true;
+    }
+
+    //100% -> 50% -> 25% -> 100%, a custom --speed goes back to 100%
+    pub fn cycle_slow_motion(&mut self) -> f64 {
+        let next = SLOW_MOTION_SPEEDS
+            .iter()
+            .position(|&speed| speed == self.speed)
+            .map_or(0, |index| (index + 1) % SLOW_MOTION_SPEEDS.len());
+
+        self.speed = SLOW_MOTION_SPEEDS[next];
+        self.pending = 0.0;
+        self.speed
+    }
+
+    //None while fast forwarding
+    pub fn multiplier(&self) -> Option<f64> {
+        if self.fast_forward {
+            None
+        } else if self.turbo {
+            Some(self.turbo_speed)
+        } else {
+            Some(self.speed)
+        }
+    }
+
+    pub fn budget(&mut self) -> FrameBudget {
+        if self.paused {
+            let frames = self.frame_advance as u32;
+            self.frame_advance = false;
+            return FrameBudget::Frames(frames);
+        }
+
+        match self.multiplier() {
+            None => FrameBudget::Unlimited,
+            Some(multiplier) => {
+                self.pending += multiplier;
+                let frames = self.pending.floor();
+                self.pending -= frames;
+                FrameBudget::Frames(frames as u32)
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        if self.paused {
+            return String::from("Paused");
+        }
+
+        match self.multiplier() {
+            None => String::from("Fast-forward"),
+            Some(multiplier) if self.turbo => format!("Turbo x{}", multiplier),
+            Some(multiplier) => format!("{}%", (multiplier * 100.0).round()),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::io;
+
+use minifb::{Scale, Window, WindowOptions};
+
+use crate::emulator::{
+    gameboy::Gameboy,
+    png::write_png,
+    ppu::{Ppu, LCDC_BG_TILEMAP, LCDC_WIN_ENABLE, LCDC_WIN_TILEMAP},
+    vram::{self, BG_MAP_0, BG_MAP_1, OAM_ENTRIES, TILEMAP_SIZE, TILESET_HEIGHT, TILESET_WIDTH},
+};
+
+use super::{
+    image::screenshot_path,
+    input::{Hotkey, Input},
+    osd::{draw_text, text_size},
+};
+
+const VIEWPORT_COLOR: u32 = 0xFF0000;
+const WINDOW_COLOR: u32 = 0x0080FF;
+const BACKGROUND: u32 = 0x202020;
+
+//Space between the two tile maps
+const MAP_GAP: usize = 4;
+const MAPS_WIDTH: usize = TILEMAP_SIZE * 2 + MAP_GAP;
+
+const OAM_ROWS: usize = OAM_ENTRIES / 2;
+const OAM_CELL_WIDTH: usize = 136;
+const OAM_CELL_HEIGHT: usize = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum View {
+    //The 384 tiles of 0x8000-0x97FF
+    Tiles,
+    //Both tile maps with the viewport and window outlined
+    Maps,
+    //The 40 OAM entries
+    Oam,
+}
+
+impl View {
+    fn name(self) -> &'static str {
+        match self {
+            View::Tiles => "tiles",
+            View::Maps => "maps",
+            View::Oam => "oam",
+        }
+    }
+
+    fn size(self) -> (usize, usize) {
+        match self {
+            View::Tiles => (TILESET_WIDTH, TILESET_HEIGHT),
+            View::Maps => (MAPS_WIDTH, TILEMAP_SIZE),
+            View::Oam => (OAM_CELL_WIDTH * 2, OAM_CELL_HEIGHT * OAM_ROWS),
+        }
+    }
+
+    pub fn render(self, gameboy: &Gameboy) -> Vec<u32> {
+        let ppu = gameboy.ppu.borrow();
+        match self {
+            View::Tiles => vram::render_tiles(&ppu, &gameboy.color_palette),
+            View::Maps => render_maps(&ppu, gameboy),
+            View::Oam => render_oam(&ppu, gameboy),
+        }
+    }
+}
+
+//Outline that wraps around the edges of the 256x256 map, like the viewport does
+fn draw_wrapping_rect(
+    pixels: &mut [u32],
+    stride: usize,
+    left: usize,
+    (x, y): (usize, usize),
+    (width, height): (usize, usize),
+    color: u32,
+) {
+    let mut plot = |px: usize, py: usize| {
+        pixels[(py % TILEMAP_SIZE) * stride + left + px % TILEMAP_SIZE] = color;
+    };
+
+    for offset in 0..width {
+        plot(x + offset, y);
+        plot(x + offset, y + height - 1);
+    }
+    for offset in 0..height {
+        plot(x, y + offset);
+        plot(x + width - 1, y + offset);
+    }
+}
+
+fn render_maps(ppu: &Ppu, gameboy: &Gameboy) -> Vec<u32> {
+    let mut pixels = vec![BACKGROUND; MAPS_WIDTH * TILEMAP_SIZE];
+
+    for (slot, map) in [BG_MAP_0, BG_MAP_1].into_iter().enumerate() {
+        let left = slot * (TILEMAP_SIZE + MAP_GAP);
+        let map_pixels = vram::render_tilemap(ppu, &gameboy.color_palette, map);
+        for (row, line) in map_pixels.chunks(TILEMAP_SIZE).enumerate() {
+            pixels[row * MAPS_WIDTH + left..][..TILEMAP_SIZE].copy_from_slice(line);
+        }
+        draw_text(
+            &mut pixels,
+            MAPS_WIDTH,
+            TILEMAP_SIZE,
+            left,
+            0,
+            &format!("{:04X}", map),
+        );
+    }
+
+    let slot = |bit| {
+        if ppu.get_lcdc(bit) {
+            TILEMAP_SIZE + MAP_GAP
+        } else {
+            0
+        }
+    };
+
+    draw_wrapping_rect(
+        &mut pixels,
+        MAPS_WIDTH,
+        slot(LCDC_BG_TILEMAP),
+        (ppu.scx as usize, ppu.scy as usize),
+        (160, 144),
+        VIEWPORT_COLOR,
+    );
+
+    //The window always starts at the top left of its map
+    let window_x = (ppu.wx as usize).saturating_sub(7);
+    let window_y = ppu.wy as usize;
+    if ppu.get_lcdc(LCDC_WIN_ENABLE) && window_x < 160 && window_y < 144 {
+        draw_wrapping_rect(
+            &mut pixels,
+            MAPS_WIDTH,
+            slot(LCDC_WIN_TILEMAP),
+            (0, 0),
+            (160 - window_x, 144 - window_y),
+            WINDOW_COLOR,
+        );
+    }
+
+    pixels
+}
+
+//Two columns of entries: the sprite, then index, position, tile and flags
+fn render_oam(ppu: &Ppu, gameboy: &Gameboy) -> Vec<u32> {
+    let (width, height) = View::Oam.size();
+    let mut pixels = vec![BACKGROUND; width * height];
+    let sprite_height = vram::sprite_height(ppu);
+
+    for sprite in vram::sprites(ppu) {
+        let left = (sprite.index / OAM_ROWS) * OAM_CELL_WIDTH + 4;
+        let top = (sprite.index % OAM_ROWS) * OAM_CELL_HEIGHT + 1;
+
+        let image = vram::render_sprite(ppu, &gameboy.color_palette, &sprite);
+        for (offset, pixel) in image.iter().enumerate() {
+            let color = pixel.unwrap_or(gameboy.color_palette.bg[0]);
+            pixels[(top + offset / 8) * width + left + offset % 8] = color;
+        }
+
+        let text = format!(
+            "{:02} X:{:02X} Y:{:02X} T:{:02X} P{} {}{}{}{}",
+            sprite.index,
+            sprite.x,
+            sprite.y,
+            sprite.tile,
+            sprite.uses_obp1() as u8,
+            if sprite.flip_x() { "X" } else { "-" },
+            if sprite.flip_y() { "Y" } else { "-" },
+            if sprite.behind_bg() { "B" } else { "-" },
+            if sprite.is_hidden(sprite_height) {
+                " OFF"
+            } else {
+                ""
+            },
+        );
+        let text_top = top + (sprite_height as usize).saturating_sub(text_size(&text).1) / 2;
+        draw_text(&mut pixels, width, height, left + 12, text_top, &text);
+    }
+
+    pixels
+}
+
+struct ViewWindow {
+    view: View,
+    window: Window,
+}
+
+//Extra windows showing VRAM and OAM, refreshed with every rendered frame
+pub struct Viewers {
+    windows: Vec<ViewWindow>,
+}
+
+impl Viewers {
+    pub fn new(views: &[View]) -> io::Result<Self> {
+        let mut windows = Vec::new();
+
+        for &view in views {
+            let (width, height) = view.size();
+            let window = Window::new(
+                &format!("Gameboy Emulator - {}", view.name()),
+                width,
+                height,
+                WindowOptions {
+                    scale: Scale::X2,
+                    ..WindowOptions::default()
+                },
+            )
+            .map_err(|err| io::Error::other(format!("Can't open {} viewer: {err}", view.name())))?;
+
+            windows.push(ViewWindow { view, window });
+        }
+
+        Ok(Self { windows })
+    }
+
+    //Redraws every open viewer, returns the view whose screenshot hotkey was pressed
+    pub fn update(&mut self, gameboy: &Gameboy, input: &Input) -> Option<View> {
+        self.windows.retain(|viewer| viewer.window.is_open());
+
+        let mut export = None;
+        for viewer in self.windows.iter_mut() {
+            let (width, height) = viewer.view.size();
+            let pixels = viewer.view.render(gameboy);
+            //A failed update only loses this frame of the viewer
+            let _ = viewer.window.update_with_buffer(&pixels, width, height);
+
+            if input
+                .pressed_hotkeys(&viewer.window)
+                .contains(&Hotkey::Screenshot)
+            {
+                export = Some(viewer.view);
+            }
+        }
+
+        export
+    }
+
+    //Saves the view next to the screenshots, e.g. screenshots/TETRIS_tiles-20250101-120000.png
+    pub fn export(view: View, gameboy: &Gameboy, dir: &str) -> io::Result<String> {
+        let name = format!("{}_{}", gameboy.header.title_string(), view.name());
+        let path = screenshot_path(dir, &name)?;
+        let path = path.to_string_lossy().into_owned();
+
+        let (width, height) = view.size();
+        write_png(&path, &view.render(gameboy), width, height)?;
+
+        Ok(path)
+    }
+}
@@ -0,0 +1,151 @@
+use log::{error, info};
+
+use super::bus::Bus;
+use super::ppu::{BGP, LCDC, SCY};
+
+//High level replacement for the DMG boot rom, no Nintendo code or data is used:
+//the logo comes from the cartridge header and the timing is approximated
+
+const LOGO: u16 = 0x0104;
+const LOGO_SIZE: u16 = 48;
+const HEADER_START: u16 = 0x0134;
+const HEADER_END: u16 = 0x014C;
+const HEADER_CHECKSUM: u16 = 0x014D;
+
+const LOGO_TILES: u16 = 0x8010;
+const REGISTERED_TILE: u8 = 0x19;
+const LOGO_MAP_TOP: u16 = 0x9904;
+const LOGO_MAP_BOTTOM: u16 = 0x9924;
+
+//Registered trademark sign drawn right of the logo
+const REGISTERED: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+//The logo starts above the screen and scrolls down one line per frame
+const START_SCROLL: u8 = 0x64;
+//Frames the logo stays still once it stopped
+const HOLD_FRAMES: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootStep {
+    Running,
+    //Header is fine, hand over to the cartridge at 0x0100
+    Done,
+    //Bad header checksum, the real boot rom hangs forever
+    LockUp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BootState {
+    Scrolling,
+    Holding(u32),
+}
+
+pub struct BootSequence {
+    state: BootState,
+}
+
+//Each logo bit becomes two pixels, 0b1011 -> 0b11001111
+fn double_bits(nibble: u8) -> u8 {
+    (0..4).fold(0, |doubled, bit| {
+        if nibble & (1 << bit) != 0 {
+            doubled | (0b11 << (bit * 2))
+        } else {
+            doubled
+        }
+    })
+}
+
+//Every logo byte is two rows of 4 pixels, doubled in both directions into half a tile
+fn load_logo(bus: &mut Bus) {
+    let mut address = LOGO_TILES;
+
+    for offset in 0..LOGO_SIZE {
+        let byte = bus.read_byte(LOGO + offset);
+
+        for nibble in [byte >> 4, byte & 0x0F] {
+            let row = double_bits(nibble);
+            for _ in 0..2 {
+                bus.write_byte(address, row);
+                bus.write_byte(address + 1, 0);
+                address += 2;
+            }
+        }
+    }
+
+    let registered = LOGO_TILES + (REGISTERED_TILE as u16 - 1) * 16;
+    for (row, &byte) in REGISTERED.iter().enumerate() {
+        bus.write_byte(registered + row as u16 * 2, byte);
+        bus.write_byte(registered + row as u16 * 2 + 1, 0);
+    }
+
+    for tile in 0..12 {
+        bus.write_byte(LOGO_MAP_TOP + tile, tile as u8 + 1);
+        bus.write_byte(LOGO_MAP_BOTTOM + tile, tile as u8 + 13);
+    }
+    bus.write_byte(LOGO_MAP_TOP + 12, REGISTERED_TILE);
+}
+
+//Same sum the boot rom checks: x = x - byte - 1 over 0x134-0x14C
+pub fn header_checksum(bus: &Bus) -> u8 {
+    (HEADER_START..=HEADER_END).fold(0u8, |checksum, address| {
+        checksum
+            .wrapping_sub(bus.read_byte(address))
+            .wrapping_sub(1)
+    })
+}
+
+impl BootSequence {
+    pub fn new(bus: &mut Bus) -> Self {
+        bus.write_byte(LCDC, 0x00);
+        load_logo(bus);
+
+        bus.write_byte(BGP, 0xFC);
+        bus.write_byte(SCY, START_SCROLL);
+        bus.write_byte(LCDC, 0x91);
+
+        info!(target: "Boot", "Running built-in boot sequence");
+
+        Self {
+            state: BootState::Scrolling,
+        }
+    }
+
+    //Advances the animation, called once per rendered frame
+    pub fn next_frame(&mut self, bus: &mut Bus) -> BootStep {
+        match self.state {
+            BootState::Scrolling => {
+                let scroll = bus.read_byte(SCY).saturating_sub(1);
+                bus.write_byte(SCY, scroll);
+
+                //The real boot rom plays its two notes here, that needs an APU
+                if scroll == 0 {
+                    self.state = BootState::Holding(HOLD_FRAMES);
+                }
+
+                BootStep::Running
+            }
+            BootState::Holding(0) => self.finish(bus),
+            BootState::Holding(frames) => {
+                self.state = BootState::Holding(frames - 1);
+                BootStep::Running
+            }
+        }
+    }
+
+    fn finish(&self, bus: &Bus) -> BootStep {
+        let expected = bus.read_byte(HEADER_CHECKSUM);
+        let checksum = header_checksum(bus);
+
+        if checksum != expected {
+            error!(
+                target: "Boot",
+                "Header checksum {:02X} doesn't match {:02X}, locking up",
+                expected,
+                checksum
+            );
+            return BootStep::LockUp;
+        }
+
+        BootStep::Done
+    }
+}
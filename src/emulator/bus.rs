@@ -1,30 +1,48 @@
-use std::fs::{self, File};
-use std::io::Read;
-use std::io::{self};
+use std::cell::Cell;
+use std::fs;
+use std::io::{self, ErrorKind};
 
-use log::warn;
+use log::{info, warn};
 
+use super::cheats::RomPatch;
+use super::debug::{StopReason, Watchpoint};
+use super::events::{EventLog, HardwareEvent};
 use super::gameboy::Shared;
 use super::interrupt::{InterruptState, ADDRESS_IF};
 use super::joypad::{Joypad, JOYPAD};
 use super::mbcs::mbc::MBC;
 use super::ppu::{Ppu, BGP, DMA_TRANSFER, LCDC, LY, LYC, OBP0, OBP1, SCX, SCY, STAT, WX, WY};
+use super::savestate::{invalid, SaveState, StateReader, StateWriter};
 use super::timer::{Timer, DIV, TAC, TIMA, TMA};
 
+pub const BOOTROM_REGISTER: u16 = 0xFF50;
+
+//DMG, MGB and SGB boot roms
+pub const DMG_BOOTROM_SIZE: usize = 0x100;
+//CGB boot rom, 0x0100-0x01FF is left to the cartridge header
+pub const CGB_BOOTROM_SIZE: usize = 0x900;
+
 pub struct Bus {
     mbc: Box<dyn MBC>,
 
-    pub dma_transfer_active: bool,
-    dma_transfer_cycle: i32,
-    dma_transfer: u8,
-
     pub interrupt: Shared<InterruptState>,
     pub ppu: Shared<Ppu>,
     pub timer: Shared<Timer>,
     pub joypad: Shared<Joypad>,
 
+    pub watchpoints: Vec<Watchpoint>,
+    //First watchpoint hit since the last take_watch_hit
+    watch_hit: Cell<Option<StopReason>>,
+
+    //Enabled Game Genie codes
+    rom_patches: Vec<RomPatch>,
+
+    pub events: Option<EventLog>,
+    //Interrupts requested since the last take_requested_interrupts
+    requested_interrupts: u8,
+
     bootrom_enable: bool,
-    bootrom: [u8; 0x100],
+    bootrom: Vec<u8>,
     wram: [u8; 0x2000],
     hram: [u8; 127],
 }
@@ -38,17 +56,21 @@ impl Bus {
         joypad: Shared<Joypad>,
     ) -> Bus {
         Bus {
-            dma_transfer_active: false,
-            dma_transfer_cycle: 0,
-            dma_transfer: 0,
-
             interrupt,
             ppu,
             timer,
             joypad,
 
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+
+            rom_patches: Vec::new(),
+
+            events: None,
+            requested_interrupts: 0,
+
             bootrom_enable: false,
-            bootrom: [0u8; 0x100],
+            bootrom: Vec::new(),
             wram: [0u8; 0x2000],
             hram: [0u8; 127],
 
@@ -56,9 +78,69 @@ impl Bus {
         }
     }
 
+    pub fn bootrom_enabled(&self) -> bool {
+        self.bootrom_enable
+    }
+
+    fn is_bootrom_mapped(&self, address: u16) -> bool {
+        if !self.bootrom_enable {
+            return false;
+        }
+
+        match address {
+            0x0000..=0x00FF => true,
+            0x0200..=0x08FF => self.bootrom.len() == CGB_BOOTROM_SIZE,
+            _ => false,
+        }
+    }
+
+    fn check_watchpoints(&self, address: u16, value: u8, write: bool) {
+        let hit = self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.matches(address, value, write));
+
+        if hit && self.watch_hit.get().is_none() {
+            self.watch_hit.set(Some(StopReason::Watchpoint {
+                address,
+                value,
+                write,
+            }));
+        }
+    }
+
+    pub fn take_watch_hit(&self) -> Option<StopReason> {
+        self.watch_hit.take()
+    }
+
     pub fn read_byte(&self, address: u16) -> u8 {
+        let value = self.peek_byte(address);
+
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(address, value, false);
+        }
+
+        value
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let value = self.mbc.read_byte(address);
+
+        self.rom_patches
+            .iter()
+            .find_map(|patch| patch.apply(address, value))
+            .unwrap_or(value)
+    }
+
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.rom_patches = patches;
+    }
+
+    //Reads without triggering watchpoints, for debugging tools
+    pub fn peek_byte(&self, address: u16) -> u8 {
         match address {
-            0x0000..=0x7FFF => self.mbc.read_byte(address),
+            0x0000..=0x08FF if self.is_bootrom_mapped(address) => self.bootrom[address as usize],
+            0x0000..=0x7FFF => self.read_rom(address),
             0x8000..=0x9FFF => self.ppu.borrow().read_vram(address),
             0xA000..=0xBFFF => self.mbc.read_byte(address),
             0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize],
@@ -72,8 +154,17 @@ impl Bus {
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(address, value, true);
+        }
+
+        self.poke_byte(address, value);
+    }
+
+    //Writes without triggering watchpoints, for cheats
+    pub fn poke_byte(&mut self, address: u16, value: u8) {
         match address {
-            0x0000..=0x7FFF => self.mbc.write_byte(address, value),
+            0x0000..=0x7FFF => self.write_mbc_register(address, value),
             0x8000..=0x9FFF => self.ppu.borrow_mut().write_vram(address, value),
             0xA000..=0xBFFF => self.mbc.write_byte(address, value),
             0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize] = value,
@@ -86,6 +177,50 @@ impl Bus {
         }
     }
 
+    fn write_mbc_register(&mut self, address: u16, value: u8) {
+        let Some(events) = &mut self.events else {
+            self.mbc.write_byte(address, value);
+            return;
+        };
+
+        let (rom_bank, ram_bank) = (self.mbc.rom_bank(), self.mbc.ram_bank());
+        self.mbc.write_byte(address, value);
+
+        if self.mbc.rom_bank() != rom_bank {
+            events.record(HardwareEvent::RomBank {
+                bank: self.mbc.rom_bank(),
+            });
+        }
+        if self.mbc.ram_bank() != ram_bank {
+            events.record(HardwareEvent::RamBank {
+                bank: self.mbc.ram_bank(),
+            });
+        }
+    }
+
+    fn record_register_write(&mut self, address: u16, value: u8) {
+        let Some(events) = &mut self.events else {
+            return;
+        };
+
+        let register = match address {
+            LCDC => "LCDC",
+            STAT => "STAT",
+            BGP => "BGP",
+            OBP0 => "OBP0",
+            OBP1 => "OBP1",
+            DMA_TRANSFER => {
+                events.record(HardwareEvent::OamDma {
+                    source: (value as u16) << 8,
+                });
+                return;
+            }
+            _ => return,
+        };
+
+        events.record(HardwareEvent::RegisterWrite { register, value });
+    }
+
     pub fn read_register(&self, address: u16) -> u8 {
         match address {
             JOYPAD => self.joypad.borrow().read_joypad(),
@@ -105,7 +240,7 @@ impl Bus {
             OBP1 => self.ppu.borrow().obp1,
             WY => self.ppu.borrow().wy,
             WX => self.ppu.borrow().wx,
-            0xFF50 => !self.bootrom_enable as u8,
+            BOOTROM_REGISTER => !self.bootrom_enable as u8,
             _ => {
                 warn!(target: "Bus","Invalid or unimplement IO register {:04X}", address);
                 0
@@ -114,6 +249,10 @@ impl Bus {
     }
 
     pub fn write_register(&mut self, address: u16, value: u8) {
+        if self.events.is_some() {
+            self.record_register_write(address, value);
+        }
+
         match address {
             JOYPAD => self.joypad.borrow_mut().write_joypad(value),
             DIV => self.timer.borrow_mut().div_reset(),
@@ -133,27 +272,200 @@ impl Bus {
             OBP1 => self.ppu.borrow_mut().obp1 = value,
             WY => self.ppu.borrow_mut().wy = value,
             WX => self.ppu.borrow_mut().wx = value,
-            0xFF50 => self.bootrom_enable = value == 0,
+            //Can't be mapped back in once disabled
+            BOOTROM_REGISTER => self.bootrom_enable &= value == 0,
             _ => warn!(target: "Bus","Invalid or unimplement IO register {:04X}", address),
         }
     }
 
+    //Overlays the boot rom on the cartridge until 0xFF50 is written
     pub fn load_bootrom(&mut self, path: &str) -> io::Result<()> {
-        let mut file = File::open(path)?;
-        let length: usize = fs::metadata(path)?.len() as usize;
-        let mut bootrom: Vec<u8> = vec![0u8; length];
-        file.read_exact(&mut bootrom)?;
+        let bootrom = fs::read(path).map_err(|err| {
+            io::Error::new(err.kind(), format!("Can't read boot rom {path}: {err}"))
+        })?;
 
-        self.bootrom_enable = true;
-
-        for (i, &byte) in bootrom.iter().enumerate() {
-            self.bootrom[i] = byte;
+        match bootrom.len() {
+            DMG_BOOTROM_SIZE => info!(target: "Bus", "Loaded DMG boot rom {}", path),
+            CGB_BOOTROM_SIZE => info!(target: "Bus", "Loaded CGB boot rom {}", path),
+            size => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Boot rom {} is {} bytes, expected {} (DMG/MGB/SGB) or {} (CGB)",
+                        path, size, DMG_BOOTROM_SIZE, CGB_BOOTROM_SIZE
+                    ),
+                ))
+            }
         }
 
+        self.bootrom = bootrom;
+        self.bootrom_enable = true;
+
         Ok(())
     }
 
+    pub fn mbc(&self) -> &dyn MBC {
+        self.mbc.as_ref()
+    }
+
+    pub fn mbc_mut(&mut self) -> &mut dyn MBC {
+        self.mbc.as_mut()
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank()
+    }
+
+    pub fn ram_bank(&self) -> usize {
+        self.mbc.ram_bank()
+    }
+
+    //Bank of the memory mapped at address, None outside the switchable regions
+    pub fn bank_at(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000..=0x3FFF => Some(0),
+            0x4000..=0x7FFF => Some(self.rom_bank()),
+            0xA000..=0xBFFF => Some(self.ram_bank()),
+            _ => None,
+        }
+    }
+
     pub fn request_interrupt(&mut self, interrupt: u8) {
         self.interrupt.borrow_mut().interrupt_flag |= 1 << interrupt;
+        self.requested_interrupts |= 1 << interrupt;
+    }
+
+    //Also counts requests for interrupts that were already pending
+    pub fn take_requested_interrupts(&mut self) -> u8 {
+        std::mem::take(&mut self.requested_interrupts)
+    }
+}
+
+//The cartridge is saved in its own section, see Gameboy::save_state
+impl SaveState for Bus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.bootrom_enable);
+        state.bytes(&self.wram);
+        state.bytes(&self.hram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        let bootrom_enable = state.bool()?;
+        if bootrom_enable && self.bootrom.is_empty() {
+            return Err(invalid(
+                "State was saved while the boot rom was running, load one with --bootrom",
+            ));
+        }
+
+        self.bootrom_enable = bootrom_enable;
+        state.fill(&mut self.wram)?;
+        state.fill(&mut self.hram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::emulator::debug::Access;
+    use crate::emulator::mbcs::rom::Rom;
+
+    fn bus() -> Bus {
+        Bus::new(
+            Box::new(Rom::new(vec![0xAB; 0x8000])),
+            Rc::new(RefCell::new(InterruptState::default())),
+            Rc::new(RefCell::new(Ppu::default())),
+            Rc::new(RefCell::new(Timer::default())),
+            Rc::new(RefCell::new(Joypad::default())),
+        )
+    }
+
+    //Loads a boot rom filled with 0x42 through a file, like --bootrom does
+    fn load_bootrom(bus: &mut Bus, name: &str, size: usize) -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("gameboy-{}-{name}", std::process::id()));
+        fs::write(&path, vec![0x42; size]).unwrap();
+
+        let result = bus.load_bootrom(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn bootrom_sizes() {
+        let mut bus = bus();
+        assert!(load_bootrom(&mut bus, "dmg.bin", DMG_BOOTROM_SIZE).is_ok());
+        assert!(load_bootrom(&mut bus, "cgb.bin", CGB_BOOTROM_SIZE).is_ok());
+
+        let err = load_bootrom(&mut bus, "short.bin", 0x80).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(load_bootrom(&mut bus, "long.bin", 0x1000).is_err());
+        assert!(bus.load_bootrom("missing/boot.bin").is_err());
+    }
+
+    #[test]
+    fn dmg_bootrom_mapping() {
+        let mut bus = bus();
+        load_bootrom(&mut bus, "dmg.bin", DMG_BOOTROM_SIZE).unwrap();
+
+        assert_eq!(bus.read_byte(0x0000), 0x42);
+        assert_eq!(bus.read_byte(0x00FF), 0x42);
+        assert_eq!(bus.read_byte(0x0100), 0xAB);
+        assert_eq!(bus.read_byte(0x0200), 0xAB);
+    }
+
+    #[test]
+    fn cgb_bootrom_leaves_the_header_to_the_cartridge() {
+        let mut bus = bus();
+        load_bootrom(&mut bus, "cgb.bin", CGB_BOOTROM_SIZE).unwrap();
+
+        assert_eq!(bus.read_byte(0x00FF), 0x42);
+        assert_eq!(bus.read_byte(0x0100), 0xAB);
+        assert_eq!(bus.read_byte(0x01FF), 0xAB);
+        assert_eq!(bus.read_byte(0x0200), 0x42);
+        assert_eq!(bus.read_byte(0x08FF), 0x42);
+        assert_eq!(bus.read_byte(0x0900), 0xAB);
+    }
+
+    #[test]
+    fn ff50_unmaps_the_bootrom_for_good() {
+        let mut bus = bus();
+        load_bootrom(&mut bus, "cgb.bin", CGB_BOOTROM_SIZE).unwrap();
+        assert!(bus.bootrom_enabled());
+        assert_eq!(bus.read_byte(BOOTROM_REGISTER), 0);
+
+        //Writing 0 leaves it mapped
+        bus.write_byte(BOOTROM_REGISTER, 0);
+        assert_eq!(bus.read_byte(0x0000), 0x42);
+
+        bus.write_byte(BOOTROM_REGISTER, 1);
+        assert!(!bus.bootrom_enabled());
+        assert_eq!(bus.read_byte(BOOTROM_REGISTER), 1);
+        assert_eq!(bus.read_byte(0x0000), 0xAB);
+        assert_eq!(bus.read_byte(0x0200), 0xAB);
+
+        bus.write_byte(BOOTROM_REGISTER, 0);
+        assert_eq!(bus.read_byte(0x0000), 0xAB);
+    }
+
+    #[test]
+    fn poke_skips_watchpoints() {
+        let mut bus = bus();
+        bus.watchpoints.push(Watchpoint::new(0xC000, Access::Write));
+
+        bus.poke_byte(0xC000, 0x12);
+        assert_eq!(bus.peek_byte(0xC000), 0x12);
+        assert!(bus.take_watch_hit().is_none());
+
+        bus.write_byte(0xC000, 0x34);
+        assert!(matches!(
+            bus.take_watch_hit(),
+            Some(StopReason::Watchpoint {
+                address: 0xC000,
+                value: 0x34,
+                write: true
+            })
+        ));
     }
 }
@@ -0,0 +1,264 @@
+use std::fmt;
+
+use super::{debug::Location, symbols::Symbols};
+
+//Frames beyond this are dropped from the bottom, code that never returns would grow it forever
+pub const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    Rst,
+    Interrupt(u8),
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallKind::Call => write!(f, "CALL"),
+            CallKind::Rst => write!(f, "RST"),
+            CallKind::Interrupt(interrupt) => write!(f, "INT{}", interrupt),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    pub target: Location,
+    //Bank mapped when the call was made
+    pub return_to: Location,
+    //SP right after the return address was pushed
+    pub sp: u16,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ret {} {}", self.target, self.return_to, self.kind)
+    }
+}
+
+impl CallFrame {
+    //Like Display, with labels where there are any
+    pub fn describe(&self, symbols: &Symbols) -> String {
+        format!(
+            "{} ret {} {}",
+            symbols.describe(self.target),
+            symbols.describe(self.return_to),
+            self.kind
+        )
+    }
+}
+
+//A RET that doesn't line up with the shadow stack, usually SP or the stack being edited by hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackMismatch {
+    //The frame was popped but its return address had been replaced
+    ReturnAddress { expected: u16, actual: u16 },
+    //Returned past routines that never returned themselves, they were dropped
+    Unwound { frames: usize },
+    //Nothing was called at that SP, e.g. PUSH then RET used as a jump
+    NoFrame { address: u16 },
+}
+
+impl fmt::Display for StackMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackMismatch::ReturnAddress { expected, actual } => write!(
+                f,
+                "returned to ${:04X} instead of ${:04X}",
+                actual, expected
+            ),
+            StackMismatch::Unwound { frames } => {
+                write!(f, "returned past {} unfinished calls", frames)
+            }
+            StackMismatch::NoFrame { address } => {
+                write!(f, "returned to ${:04X} without a call", address)
+            }
+        }
+    }
+}
+
+//Shadow of the calls on the Gameboy stack, innermost last
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+}
+
+impl CallStack {
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn push(&mut self, frame: CallFrame) {
+        //SP was moved back up without returning, e.g. reset by the game, those calls are gone
+        while self.frames.last().is_some_and(|last| last.sp <= frame.sp) {
+            self.frames.pop();
+        }
+
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+
+    //Matches a RET to its frame using SP, since the return address alone can be forged
+    pub fn pop(&mut self, address: u16, sp: u16) -> Option<StackMismatch> {
+        let popped_at = sp.wrapping_sub(2);
+
+        //Deeper frames sit below the popped address
+        let mut unwound = 0;
+        while self.frames.last().is_some_and(|frame| frame.sp < popped_at) {
+            self.frames.pop();
+            unwound += 1;
+        }
+
+        match self.frames.last() {
+            Some(frame) if frame.sp == popped_at => {
+                let expected = frame.return_to.address;
+                self.frames.pop();
+
+                if expected != address {
+                    Some(StackMismatch::ReturnAddress {
+                        expected,
+                        actual: address,
+                    })
+                } else if unwound > 0 {
+                    Some(StackMismatch::Unwound { frames: unwound })
+                } else {
+                    None
+                }
+            }
+            _ if unwound > 0 => Some(StackMismatch::Unwound { frames: unwound }),
+            _ => Some(StackMismatch::NoFrame { address }),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(target: u16, return_to: u16, sp: u16) -> CallFrame {
+        CallFrame {
+            kind: CallKind::Call,
+            target: Location {
+                bank: Some(0),
+                address: target,
+            },
+            return_to: Location {
+                bank: Some(0),
+                address: return_to,
+            },
+            sp,
+        }
+    }
+
+    fn targets(stack: &CallStack) -> Vec<u16> {
+        stack
+            .frames()
+            .iter()
+            .map(|frame| frame.target.address)
+            .collect()
+    }
+
+    #[test]
+    fn nested_calls() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0200, 0x0153, 0xFFFC));
+        stack.push(frame(0x0300, 0x0203, 0xFFFA));
+        assert_eq!(targets(&stack), [0x0200, 0x0300]);
+
+        //SP after the RET is 2 above the frame
+        assert_eq!(stack.pop(0x0203, 0xFFFC), None);
+        assert_eq!(targets(&stack), [0x0200]);
+        assert_eq!(stack.pop(0x0153, 0xFFFE), None);
+        assert!(stack.frames().is_empty());
+    }
+
+    #[test]
+    fn forged_return_address() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0200, 0x0153, 0xFFFC));
+
+        assert_eq!(
+            stack.pop(0x0400, 0xFFFE),
+            Some(StackMismatch::ReturnAddress {
+                expected: 0x0153,
+                actual: 0x0400
+            })
+        );
+        assert!(stack.frames().is_empty());
+    }
+
+    #[test]
+    fn returning_past_unfinished_calls() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0200, 0x0153, 0xFFFC));
+        stack.push(frame(0x0300, 0x0203, 0xFFFA));
+        stack.push(frame(0x0400, 0x0303, 0xFFF8));
+
+        //ADD SP,4 then RET skips the two inner frames
+        assert_eq!(
+            stack.pop(0x0153, 0xFFFE),
+            Some(StackMismatch::Unwound { frames: 2 })
+        );
+        assert!(stack.frames().is_empty());
+    }
+
+    #[test]
+    fn return_without_a_call() {
+        let mut stack = CallStack::default();
+        assert_eq!(
+            stack.pop(0x1234, 0xFFFE),
+            Some(StackMismatch::NoFrame { address: 0x1234 })
+        );
+
+        //PUSH then RET inside a routine leaves its frame alone
+        stack.push(frame(0x0200, 0x0153, 0xFFFC));
+        assert_eq!(
+            stack.pop(0x0250, 0xFFFA),
+            Some(StackMismatch::NoFrame { address: 0x0250 })
+        );
+        assert_eq!(targets(&stack), [0x0200]);
+    }
+
+    #[test]
+    fn sp_reset_drops_the_old_frames() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0200, 0x0153, 0xFFFC));
+        stack.push(frame(0x0300, 0x0203, 0xFFFA));
+
+        //LD SP,$FFFE then CALL pushes at the same SP as the outer frame
+        stack.push(frame(0x0500, 0x0160, 0xFFFC));
+        assert_eq!(targets(&stack), [0x0500]);
+    }
+
+    #[test]
+    fn depth_is_capped() {
+        let mut stack = CallStack::default();
+        for depth in 0..MAX_DEPTH as u16 + 10 {
+            stack.push(frame(depth, 0, 0xFFFC - depth * 2));
+        }
+
+        assert_eq!(stack.frames().len(), MAX_DEPTH);
+        assert_eq!(stack.frames()[0].target.address, 10);
+    }
+
+    #[test]
+    fn mismatch_messages() {
+        let forged = StackMismatch::ReturnAddress {
+            expected: 0x0153,
+            actual: 0x0400,
+        };
+        assert_eq!(forged.to_string(), "returned to $0400 instead of $0153");
+        assert_eq!(
+            StackMismatch::Unwound { frames: 2 }.to_string(),
+            "returned past 2 unfinished calls"
+        );
+    }
+}
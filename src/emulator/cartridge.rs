@@ -9,8 +9,77 @@ use crate::emulator::mbcs::mbc5::MBC5;
 
 use super::mbcs::{mbc::MBC, mbc1::MBC1, rom::Rom};
 
+pub const CART_HEADER_TITLE: u16 = 0x134;
+pub const CART_HEADER_CGB_FLAG: u16 = 0x143;
+pub const CART_HEADER_NEW_LICENSEE: u16 = 0x144;
 pub const CART_HEADER_TYPE: u16 = 0x147;
 pub const CART_HEADER_RAM_SIZE: u16 = 0x148;
+pub const CART_HEADER_OLD_LICENSEE: u16 = 0x14B;
+pub const CART_HEADER_CHECKSUM: u16 = 0x14D;
+pub const CART_HEADER_GLOBAL_CHECKSUM: u16 = 0x14E;
+
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+    //NOTE: 16 bytes including the CGB flag, which is what the CGB boot rom hashes
+    pub title: [u8; 16],
+    pub cgb_flag: u8,
+    pub new_licensee: [u8; 2],
+    pub cartridge_type: u8,
+    pub old_licensee: u8,
+    pub header_checksum: u8,
+    //Sum of every ROM byte but these two, big endian
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn read(mbc: &dyn MBC) -> Self {
+        let mut title = [0u8; 16];
+        for (i, byte) in title.iter_mut().enumerate() {
+            *byte = mbc.read_byte(CART_HEADER_TITLE + i as u16);
+        }
+
+        Self {
+            title,
+            cgb_flag: mbc.read_byte(CART_HEADER_CGB_FLAG),
+            new_licensee: [
+                mbc.read_byte(CART_HEADER_NEW_LICENSEE),
+                mbc.read_byte(CART_HEADER_NEW_LICENSEE + 1),
+            ],
+            cartridge_type: mbc.read_byte(CART_HEADER_TYPE),
+            old_licensee: mbc.read_byte(CART_HEADER_OLD_LICENSEE),
+            header_checksum: mbc.read_byte(CART_HEADER_CHECKSUM),
+            global_checksum: u16::from_be_bytes([
+                mbc.read_byte(CART_HEADER_GLOBAL_CHECKSUM),
+                mbc.read_byte(CART_HEADER_GLOBAL_CHECKSUM + 1),
+            ]),
+        }
+    }
+
+    pub fn title_string(&self) -> String {
+        self.title
+            .iter()
+            .take_while(|&&byte| byte != 0)
+            .filter(|byte| byte.is_ascii_graphic() || **byte == b' ')
+            .map(|&byte| byte as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    pub fn supports_cgb(&self) -> bool {
+        self.cgb_flag & 0x80 != 0
+    }
+
+    pub fn is_nintendo(&self) -> bool {
+        self.old_licensee == 0x01 || (self.old_licensee == 0x33 && self.new_licensee == *b"01")
+    }
+
+    pub fn title_checksum(&self) -> u8 {
+        self.title
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+    }
+}
 
 pub fn load_cartridge(path: &str) -> io::Result<Box<dyn MBC>> {
     let mut file = File::open(path)?;
@@ -0,0 +1,288 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use log::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    //Replaces a ROM byte on reads, only when it currently holds compare
+    GameGenie {
+        address: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    //Written to RAM once per frame
+    GameShark {
+        address: u16,
+        value: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub kind: CheatKind,
+}
+
+//ROM patch applied by the bus, the enabled Game Genie codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomPatch {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl RomPatch {
+    pub fn apply(&self, address: u16, value: u8) -> Option<u8> {
+        if address == self.address && self.compare.is_none_or(|compare| compare == value) {
+            Some(self.value)
+        } else {
+            None
+        }
+    }
+}
+
+fn invalid(code: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid cheat \"{}\": {}", code, reason),
+    )
+}
+
+fn hex_digits(code: &str) -> Option<Vec<u8>> {
+    code.chars()
+        .filter(|&c| c != '-')
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect()
+}
+
+//ABC-DEF or ABC-DEF-GHI: AB is the value, FCDE the address with F inverted,
+//and GI the compare byte rotated right by 2 and xored with 0xBA (H is unused)
+fn parse_game_genie(code: &str) -> io::Result<CheatKind> {
+    let groups: Vec<&str> = code.split('-').collect();
+    if !matches!(groups.len(), 2 | 3) || groups.iter().any(|group| group.len() != 3) {
+        return Err(invalid(
+            code,
+            "Game Genie codes look like ABC-DEF or ABC-DEF-GHI",
+        ));
+    }
+
+    let digits = hex_digits(code).ok_or_else(|| invalid(code, "not a hex number"))?;
+
+    let value = (digits[0] << 4) | digits[1];
+    let address = (((digits[5] ^ 0xF) as u16) << 12)
+        | ((digits[2] as u16) << 8)
+        | ((digits[3] as u16) << 4)
+        | digits[4] as u16;
+
+    if address > 0x7FFF {
+        return Err(invalid(
+            code,
+            "Game Genie codes can only patch ROM (0000-7FFF)",
+        ));
+    }
+
+    let compare = (digits.len() == 9).then(|| {
+        let byte = (digits[6] << 4) | digits[8];
+        byte.rotate_right(2) ^ 0xBA
+    });
+
+    Ok(CheatKind::GameGenie {
+        address,
+        value,
+        compare,
+    })
+}
+
+//TTVVAAAA: type 01 (00 on some devices), value, address low byte first
+fn parse_game_shark(code: &str) -> io::Result<CheatKind> {
+    let digits = hex_digits(code).ok_or_else(|| invalid(code, "not a hex number"))?;
+    let byte = |index: usize| (digits[index * 2] << 4) | digits[index * 2 + 1];
+
+    if byte(0) > 0x01 {
+        return Err(invalid(
+            code,
+            &format!("unsupported GameShark type {:02X}, only 01 is", byte(0)),
+        ));
+    }
+
+    let address = ((byte(3) as u16) << 8) | byte(2) as u16;
+    if !matches!(address, 0xA000..=0xDFFF | 0xFF80..=0xFFFE) {
+        return Err(invalid(
+            code,
+            "GameShark codes can only write RAM (A000-DFFF, FF80-FFFE)",
+        ));
+    }
+
+    Ok(CheatKind::GameShark {
+        address,
+        value: byte(1),
+    })
+}
+
+pub fn parse_code(code: &str) -> io::Result<CheatKind> {
+    let code = code.trim();
+
+    if code.contains('-') {
+        parse_game_genie(code)
+    } else if code.len() == 8 {
+        parse_game_shark(code)
+    } else {
+        Err(invalid(
+            code,
+            "expected a Game Genie (ABC-DEF-GHI) or GameShark (01VVAAAA) code",
+        ))
+    }
+}
+
+impl Cheat {
+    pub fn new(code: &str, name: &str) -> io::Result<Self> {
+        Ok(Self {
+            code: code.trim().to_ascii_uppercase(),
+            name: name.trim().to_string(),
+            enabled: true,
+            kind: parse_code(code)?,
+        })
+    }
+}
+
+//One cheat per line, "CODE description", prefixed with ! to start disabled. # starts a comment
+pub fn parse_cheats(text: &str) -> io::Result<Vec<Cheat>> {
+    let mut cheats = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (enabled, line) = match line.strip_prefix('!') {
+            Some(line) => (false, line.trim_start()),
+            None => (true, line),
+        };
+        let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        let mut cheat = Cheat::new(code, name)
+            .map_err(|err| io::Error::new(err.kind(), format!("Line {}: {}", number + 1, err)))?;
+        cheat.enabled = enabled;
+        cheats.push(cheat);
+    }
+
+    Ok(cheats)
+}
+
+pub fn load_cheats(path: &Path) -> io::Result<Vec<Cheat>> {
+    let text = fs::read_to_string(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't read cheats {}: {}", path.display(), err),
+        )
+    })?;
+
+    let cheats = parse_cheats(&text)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    info!(target: "Cheats", "Loaded {} cheats from {}", cheats.len(), path.display());
+
+    Ok(cheats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_genie_with_compare() {
+        assert_eq!(
+            parse_code("00A-17B-C49").unwrap(),
+            CheatKind::GameGenie {
+                address: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8),
+            }
+        );
+    }
+
+    #[test]
+    fn game_genie_without_compare() {
+        assert_eq!(
+            parse_code("3EA-17B").unwrap(),
+            CheatKind::GameGenie {
+                address: 0x4A17,
+                value: 0x3E,
+                compare: None,
+            }
+        );
+    }
+
+    #[test]
+    fn game_shark() {
+        assert_eq!(
+            parse_code("010FA0C0").unwrap(),
+            CheatKind::GameShark {
+                address: 0xC0A0,
+                value: 0x0F,
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_codes() {
+        for code in [
+            "00A-17",
+            "00A-17B-C4",
+            "00A-17B-C49-000",
+            "00G-17B",
+            "00A-176",
+            "020FA0C0",
+            "010F0080",
+            "010FA0CZ",
+            "1234",
+            "",
+        ] {
+            assert!(parse_code(code).is_err(), "{code} was accepted");
+        }
+    }
+
+    #[test]
+    fn cheat_file() {
+        let cheats = parse_cheats(
+            "# Infinite lives\n\
+             00A-17B-C49 Lives\n\
+             \n\
+             ! 010FA0C0   Health\n",
+        )
+        .unwrap();
+
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(cheats[0].name, "Lives");
+        assert!(cheats[0].enabled);
+        assert_eq!(cheats[1].code, "010FA0C0");
+        assert_eq!(cheats[1].name, "Health");
+        assert!(!cheats[1].enabled);
+    }
+
+    #[test]
+    fn cheat_file_errors_name_the_line() {
+        let err = parse_cheats("00A-17B-C49\n# comment\nXYZ-123 Broken\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("Line 3: "), "{err}");
+    }
+
+    #[test]
+    fn rom_patch_compare() {
+        let patch = RomPatch {
+            address: 0x4A17,
+            value: 0x00,
+            compare: Some(0xC8),
+        };
+
+        assert_eq!(patch.apply(0x4A17, 0xC8), Some(0x00));
+        assert_eq!(patch.apply(0x4A17, 0x12), None);
+        assert_eq!(patch.apply(0x4A18, 0xC8), None);
+    }
+}
@@ -0,0 +1,318 @@
+use super::{
+    cartridge::CartridgeHeader,
+    joypad::Joypad,
+    ppu::{LAYER_OBJ0, LAYER_OBJ1},
+};
+
+//NOTE: Colors are stored as 0x00RRGGBB, the same layout minifb expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorPalette {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+pub const GRAYSCALE: ColorPalette = ColorPalette {
+    bg: [0xFFFFFF, 0xB5B5B5, 0x686868, 0x000000],
+    obj0: [0xFFFFFF, 0xB5B5B5, 0x686868, 0x000000],
+    obj1: [0xFFFFFF, 0xB5B5B5, 0x686868, 0x000000],
+};
+
+impl Default for ColorPalette {
+    fn default() -> Self {
+        GRAYSCALE
+    }
+}
+
+impl ColorPalette {
+    pub fn color(&self, layer: u8, shade: u8) -> u32 {
+        let shade = (shade & 3) as usize;
+
+        match layer {
+            LAYER_OBJ0 => self.obj0[shade],
+            LAYER_OBJ1 => self.obj1[shade],
+            _ => self.bg[shade],
+        }
+    }
+
+    pub fn apply(&self, buffer: &[u8], layers: &[u8]) -> Vec<u32> {
+        buffer
+            .iter()
+            .zip(layers.iter())
+            .map(|(&pixel, &layer)| {
+                if pixel > 3 {
+                    panic!("Invalid pixel");
+                }
+
+                self.color(layer, pixel)
+            })
+            .collect()
+    }
+}
+
+//Debug tints for background, window, OBJ0 and OBJ1 pixels
+const LAYER_TINTS: [u32; 4] = [0xFF0000, 0x00C000, 0x0000FF, 0xFF00FF];
+
+//Halfway between the color and the tint of its layer
+pub fn tint(color: u32, layer: u8) -> u32 {
+    let tint = LAYER_TINTS[(layer & 3) as usize];
+    ((color >> 1) & 0x7F7F7F) + ((tint >> 1) & 0x7F7F7F)
+}
+
+//Raw BGR555 palettes stored in the CGB boot rom, four colors each
+const PALETTES: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, //
+    0x639F, 0x4279, 0x15B0, 0x04CB, //
+    0x7FFF, 0x6E31, 0x454A, 0x0000, //
+    0x7FFF, 0x1BEF, 0x0200, 0x0000, //
+    0x7FFF, 0x421F, 0x1CF2, 0x0000, //
+    0x7FFF, 0x5294, 0x294A, 0x0000, //
+    0x7FFF, 0x03FF, 0x012F, 0x0000, //
+    0x7FFF, 0x03EF, 0x01D6, 0x0000, //
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000, //
+    0x7E74, 0x03FF, 0x0180, 0x0000, //
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B, //
+    0x7ED6, 0x4BFF, 0x2175, 0x0000, //
+    0x53FF, 0x4A5F, 0x7E52, 0x0000, //
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0, //
+    0x03ED, 0x7FFF, 0x255F, 0x0000, //
+    0x036A, 0x021F, 0x03FF, 0x7FFF, //
+    0x7FFF, 0x01DF, 0x0112, 0x0000, //
+    0x231F, 0x035F, 0x00F2, 0x0009, //
+    0x7FFF, 0x03EA, 0x011F, 0x0000, //
+    0x299F, 0x001A, 0x000C, 0x0000, //
+    0x7FFF, 0x027F, 0x001F, 0x0000, //
+    0x7FFF, 0x03E0, 0x0206, 0x0120, //
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00, //
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F, //
+    0x7FFF, 0x03FF, 0x001F, 0x0000, //
+    0x03FF, 0x001F, 0x000C, 0x0000, //
+    0x7FFF, 0x033F, 0x0193, 0x0000, //
+    0x0000, 0x4200, 0x037F, 0x7FFF, //
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000, //
+    0x7FFF, 0x1BEF, 0x6180, 0x0000, //
+];
+
+//Offsets (in colors) into PALETTES for (OBJ0, OBJ1, BG).
+//A few entries start mid-palette, exactly like the boot rom does
+const COMBINATIONS: [(usize, usize, usize); 51] = [
+    (4 * 4, 4 * 4, 29 * 4),         // 0, Right + A
+    (18 * 4, 18 * 4, 18 * 4),       // 1, Right
+    (20 * 4, 20 * 4, 20 * 4),       // 2
+    (24 * 4, 24 * 4, 24 * 4),       // 3, Down + A
+    (9 * 4, 9 * 4, 9 * 4),          // 4
+    (0, 0, 0),                      // 5, Up
+    (27 * 4, 27 * 4, 27 * 4),       // 6, Right + B
+    (5 * 4, 5 * 4, 5 * 4),          // 7, Left + B
+    (12 * 4, 12 * 4, 12 * 4),       // 8, Down
+    (26 * 4, 26 * 4, 26 * 4),       // 9
+    (16 * 4, 8 * 4, 8 * 4),         // 10
+    (4 * 4, 28 * 4, 28 * 4),        // 11
+    (4 * 4, 2 * 4, 2 * 4),          // 12
+    (3 * 4, 4 * 4, 4 * 4),          // 13
+    (4 * 4, 29 * 4, 29 * 4),        // 14
+    (28 * 4, 4 * 4, 28 * 4),        // 15
+    (2 * 4, 17 * 4, 2 * 4),         // 16
+    (16 * 4, 16 * 4, 8 * 4),        // 17
+    (4 * 4, 4 * 4, 7 * 4),          // 18
+    (4 * 4, 4 * 4, 18 * 4),         // 19
+    (4 * 4, 4 * 4, 20 * 4),         // 20
+    (19 * 4, 19 * 4, 9 * 4),        // 21
+    (4 * 4 - 1, 4 * 4 - 1, 11 * 4), // 22
+    (17 * 4, 17 * 4, 2 * 4),        // 23
+    (4 * 4, 4 * 4, 2 * 4),          // 24
+    (4 * 4, 4 * 4, 3 * 4),          // 25
+    (28 * 4, 28 * 4, 0),            // 26
+    (3 * 4, 3 * 4, 0),              // 27
+    (0, 0, 4),                      // 28, Up + B
+    (18 * 4, 22 * 4, 18 * 4),       // 29
+    (20 * 4, 22 * 4, 20 * 4),       // 30
+    (24 * 4, 22 * 4, 24 * 4),       // 31
+    (16 * 4, 22 * 4, 8 * 4),        // 32
+    (17 * 4, 4 * 4, 13 * 4),        // 33
+    (28 * 4 - 1, 0, 14 * 4),        // 34
+    (28 * 4 - 1, 4 * 4, 15 * 4),    // 35
+    (19 * 4, 22 * 4, 9 * 4),        // 36
+    (16 * 4, 28 * 4, 10 * 4),       // 37
+    (4 * 4, 23 * 4, 28 * 4),        // 38
+    (17 * 4, 22 * 4, 2 * 4),        // 39
+    (4 * 4, 0, 2 * 4),              // 40, Left + A
+    (4 * 4, 28 * 4, 3 * 4),         // 41
+    (28 * 4, 3 * 4, 0),             // 42
+    (3 * 4, 28 * 4, 4 * 4),         // 43, Up + A
+    (21 * 4, 28 * 4, 4 * 4),        // 44
+    (3 * 4, 28 * 4, 0),             // 45
+    (25 * 4, 3 * 4, 28 * 4),        // 46
+    (0, 28 * 4, 8 * 4),             // 47
+    (4 * 4, 3 * 4, 28 * 4),         // 48, Left
+    (28 * 4, 3 * 4, 6 * 4),         // 49, Down + B
+    (4 * 4, 28 * 4, 29 * 4),        // 50
+];
+
+//Title checksums of licensed Nintendo games, see TITLE_COMBINATIONS for the palette used
+const TITLE_CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B, //
+    //Checksums below are shared by several games and need the fourth title letter
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3, 0x46,
+    0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+];
+
+const FIRST_DUPLICATE_CHECKSUM: usize = 65;
+
+const DUPLICATE_FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+const TITLE_COMBINATIONS: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29,
+    5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6,
+    5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, //
+    36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19,
+    34, 23, 18, 29,
+];
+
+fn bgr555_to_rgb(color: u16) -> u32 {
+    let expand = |value: u16| -> u32 {
+        let value = (value & 0x1F) as u32;
+        (value << 3) | (value >> 2)
+    };
+
+    (expand(color) << 16) | (expand(color >> 5) << 8) | expand(color >> 10)
+}
+
+fn palette_at(offset: usize) -> [u32; 4] {
+    let mut colors = [0u32; 4];
+    for (i, color) in colors.iter_mut().enumerate() {
+        *color = bgr555_to_rgb(PALETTES[offset + i]);
+    }
+
+    colors
+}
+
+pub fn combination(index: usize) -> ColorPalette {
+    let (obj0, obj1, bg) = COMBINATIONS[index];
+
+    ColorPalette {
+        bg: palette_at(bg),
+        obj0: palette_at(obj0),
+        obj1: palette_at(obj1),
+    }
+}
+
+pub fn title_combination(header: &CartridgeHeader) -> usize {
+    if !header.is_nintendo() {
+        return 0;
+    }
+
+    let checksum = header.title_checksum();
+    let fourth_letter = header.title[3];
+
+    TITLE_CHECKSUMS
+        .iter()
+        .enumerate()
+        .position(|(i, &sum)| {
+            sum == checksum
+                && (i < FIRST_DUPLICATE_CHECKSUM
+                    || DUPLICATE_FOURTH_LETTERS[i - FIRST_DUPLICATE_CHECKSUM] == fourth_letter)
+        })
+        .map(|i| TITLE_COMBINATIONS[i] as usize)
+        .unwrap_or(0)
+}
+
+//Palette the CGB boot rom picks for a monochrome cartridge
+pub fn compat_palette(header: &CartridgeHeader) -> ColorPalette {
+    combination(title_combination(header))
+}
+
+//Palette picked by holding a direction (optionally with A or B) while the CGB boots.
+//Joypad fields are active low, like the register itself
+pub fn manual_palette(joypad: &Joypad) -> Option<ColorPalette> {
+    let index = match (
+        !joypad.up,
+        !joypad.down,
+        !joypad.left,
+        !joypad.right,
+        !joypad.btn_a,
+        !joypad.btn_b,
+    ) {
+        (_, _, _, true, true, _) => 0,
+        (_, _, _, true, _, true) => 6,
+        (_, _, _, true, _, _) => 1,
+        (_, _, true, _, true, _) => 40,
+        (_, _, true, _, _, true) => 7,
+        (_, _, true, _, _, _) => 48,
+        (true, _, _, _, true, _) => 43,
+        (true, _, _, _, _, true) => 28,
+        (true, _, _, _, _, _) => 5,
+        (_, true, _, _, true, _) => 3,
+        (_, true, _, _, _, true) => 49,
+        (_, true, _, _, _, _) => 8,
+        _ => return None,
+    };
+
+    Some(combination(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(title: &[u8], old_licensee: u8) -> CartridgeHeader {
+        let mut padded = [0u8; 16];
+        padded[..title.len()].copy_from_slice(title);
+
+        CartridgeHeader {
+            title: padded,
+            cgb_flag: 0,
+            new_licensee: [0, 0],
+            cartridge_type: 0,
+            old_licensee,
+            header_checksum: 0,
+            global_checksum: 0,
+        }
+    }
+
+    //A Nintendo title with the given checksum and fourth letter
+    fn title_with(checksum: u8, fourth_letter: u8) -> CartridgeHeader {
+        header(
+            &[checksum.wrapping_sub(fourth_letter), 0, 0, fourth_letter],
+            0x01,
+        )
+    }
+
+    #[test]
+    fn unique_checksum() {
+        let tetris = header(b"TETRIS", 0x01);
+        assert_eq!(tetris.title_checksum(), 0xDB);
+        assert_eq!(title_combination(&tetris), 3);
+        assert_eq!(compat_palette(&tetris), combination(3));
+    }
+
+    #[test]
+    fn duplicate_checksum_uses_the_fourth_letter() {
+        assert_eq!(title_combination(&title_with(0x46, b'E')), 22);
+        assert_eq!(title_combination(&title_with(0x46, b'R')), 46);
+        assert_eq!(title_combination(&title_with(0x46, b'X')), 0);
+    }
+
+    #[test]
+    fn unknown_games_get_the_default() {
+        assert_eq!(title_combination(&header(b"TETRIS", 0x00)), 0);
+        assert_eq!(title_combination(&title_with(0x02, b'A')), 0);
+
+        //New licensee code "01" is Nintendo too
+        let mut tetris = header(b"TETRIS", 0x33);
+        tetris.new_licensee = *b"01";
+        assert_eq!(title_combination(&tetris), 3);
+    }
+
+    #[test]
+    fn bgr555() {
+        assert_eq!(bgr555_to_rgb(0x7FFF), 0xFFFFFF);
+        assert_eq!(bgr555_to_rgb(0x001F), 0xFF0000);
+        assert_eq!(bgr555_to_rgb(0x7C00), 0x0000FF);
+        assert_eq!(combination(5).bg, [0xFFFFFF, 0xFFAD63, 0x843100, 0x000000]);
+    }
+}
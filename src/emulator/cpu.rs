@@ -1,18 +1,22 @@
-use std::process::exit;
+use std::{io, process::exit};
 
-use log::{debug, info};
+use log::{debug, error, info};
 
 use super::{
     bus::Bus,
+    callstack::{CallKind, CallStack, StackMismatch},
     gameboy::Shared,
     instructions::{
         opcode::Opcode,
         opcode_table::{execute_opcode, get_opcode, get_prefixed_opcode},
+        stack::push_frame,
     },
     interrupt::{
         InterruptState, ADDRESS_IE, ADDRESS_IF, INT_JOYPAD, INT_LCD, INT_SERIAL, INT_TIMER,
         INT_VBLANK,
     },
+    savestate::{SaveState, StateReader, StateWriter},
+    trace::Tracer,
 };
 
 pub const Z: u8 = 7;
@@ -35,6 +39,19 @@ pub struct Cpu {
     pub halt: bool,
     pub halt_bug: bool,
     pub i_enable_flag: bool,
+
+    //Set after fetching an illegal opcode, the real CPU hangs until power off
+    pub locked: bool,
+
+    //Interrupt dispatched by the last step, for interrupt breakpoints
+    pub dispatched_interrupt: Option<u8>,
+
+    //Calls that haven't returned yet, and the RET of the last step that didn't match them
+    pub call_stack: CallStack,
+    pub stack_mismatch: Option<StackMismatch>,
+
+    //Per instruction log, see --trace
+    pub tracer: Option<Tracer>,
 }
 
 impl Cpu {
@@ -56,6 +73,11 @@ impl Cpu {
 
             // ime: false,
             i_enable_flag: false,
+            locked: false,
+            dispatched_interrupt: None,
+            call_stack: CallStack::default(),
+            stack_mismatch: None,
+            tracer: None,
             //
             // interrupt_enable: 0u8,
             // interrupt_flags: 0u8,
@@ -110,7 +132,33 @@ impl Cpu {
         line
     }
 
+    //Disassembles the instruction at address without executing it, returns it with its length
+    pub fn disassemble_at(bus: &Bus, address: u16) -> (String, u16) {
+        let opcode_byte = bus.peek_byte(address);
+
+        if opcode_byte == 0xCB {
+            let opcode = get_prefixed_opcode(bus.peek_byte(address.wrapping_add(1)));
+            return (Cpu::disassemble_opcode(&opcode, Vec::new()), 2);
+        }
+
+        match get_opcode(opcode_byte) {
+            Ok(opcode) => {
+                let data = (1..opcode.length as u16)
+                    .map(|offset| bus.peek_byte(address.wrapping_add(offset)))
+                    .collect();
+                (Cpu::disassemble_opcode(&opcode, data), opcode.length as u16)
+            }
+            Err(_) => (format!("DB ${:02X}", opcode_byte), 1),
+        }
+    }
+
     pub fn step(&mut self, bus: &mut Bus, interrupt: Shared<InterruptState>) -> i32 {
+        if self.locked {
+            return 4;
+        }
+
+        self.dispatched_interrupt = None;
+        self.stack_mismatch = None;
         let interrupted = self.perform_interrupt(bus, interrupt.clone());
 
         if self.halt {
@@ -149,6 +197,12 @@ impl Cpu {
             self.i_enable_flag = false;
         }
 
+        if let Some(mut tracer) = self.tracer.take() {
+            if tracer.trace(self, bus) {
+                self.tracer = Some(tracer);
+            }
+        }
+
         //Fetch
         let opcode_byte = self.next_byte(bus);
 
@@ -159,27 +213,21 @@ impl Cpu {
 
             get_prefixed_opcode(cb_opcode_byte)
         } else {
-            get_opcode(opcode_byte)
-                .unwrap_or_else(|_| panic!("Invalid opcode reached: {:02X}", opcode_byte))
+            match get_opcode(opcode_byte) {
+                Ok(opcode) => opcode,
+                Err(_) => {
+                    error!(
+                        target: "CPU",
+                        "Invalid opcode reached: {:02X} at {:04X}, locking up",
+                        opcode_byte,
+                        self.pc.wrapping_sub(1)
+                    );
+                    self.locked = true;
+                    return 4;
+                }
+            }
         };
 
-        // let mut data: Vec<u8> = vec![0u8; opcode.length - 1];
-        // let tpc = self.pc;
-        // (0..opcode.length - 1).for_each(|i| {
-        //     data[i] = bus.read_byte(tpc + i as u16);
-        // });
-        // debug!(
-        //     target: "CPU",
-        //     "{:04X} {: <20}| AF={:04X}, BC={:04X}, DE={:04X}, HL={:04X} SP={:04X}",
-        //     self.pc - 1,
-        //     Cpu::disassemble_opcode(&opcode, data),
-        //     self.af(),
-        //     self.bc(),
-        //     self.de(),
-        //     self.hl(),
-        //     self.sp
-        // );
-
         //Execute
         let time = execute_opcode(self, bus, interrupt, opcode.clone());
         self.f &= 0xF0;
@@ -233,6 +281,8 @@ impl Cpu {
 
         // bus.write_byte(ADDRESS_IF, bus.read_byte(ADDRESS_IF) & !(1 << clear_bit));
         interrupt.interrupt_flag &= !(1 << clear_bit);
+        self.dispatched_interrupt = Some(clear_bit);
+        push_frame(self, bus, CallKind::Interrupt(clear_bit), address);
 
         self.pc = address;
         true
@@ -305,3 +355,46 @@ impl Default for Cpu {
         Self::new()
     }
 }
+
+//Debugger state (call stack, trace) stays with the session
+impl SaveState for Cpu {
+    fn save_state(&self, state: &mut StateWriter) {
+        for register in [
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l,
+        ] {
+            state.u8(register);
+        }
+        state.u16(self.sp);
+        state.u16(self.pc);
+        state.bool(self.halt);
+        state.bool(self.halt_bug);
+        state.bool(self.i_enable_flag);
+        state.bool(self.locked);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        for register in [
+            &mut self.a,
+            &mut self.f,
+            &mut self.b,
+            &mut self.c,
+            &mut self.d,
+            &mut self.e,
+            &mut self.h,
+            &mut self.l,
+        ] {
+            *register = state.u8()?;
+        }
+        self.sp = state.u16()?;
+        self.pc = state.u16()?;
+        self.halt = state.bool()?;
+        self.halt_bug = state.bool()?;
+        self.i_enable_flag = state.bool()?;
+        self.locked = state.bool()?;
+
+        self.dispatched_interrupt = None;
+        self.stack_mismatch = None;
+        self.call_stack.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use super::{bus::Bus, callstack::StackMismatch};
+
+//Bank:PC of an instruction or of the entry point of a routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub bank: Option<usize>,
+    pub address: u16,
+}
+
+impl Location {
+    pub fn at(bus: &Bus, address: u16) -> Self {
+        Self {
+            bank: bus.bank_at(address),
+            address,
+        }
+    }
+
+    pub fn region(&self) -> String {
+        match (self.address, self.bank) {
+            (0x0000..=0x7FFF, Some(bank)) => format!("ROM{:X}", bank),
+            (0xA000..=0xBFFF, Some(bank)) => format!("SRAM{:X}", bank),
+            (0x8000..=0x9FFF, _) => String::from("VRAM"),
+            (0xC000..=0xDFFF, _) => String::from("WRAM"),
+            (0xFF80..=0xFFFE, _) => String::from("HRAM"),
+            _ => String::from("----"),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{:04X}", self.region(), self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    //Stops before the instruction at address runs, bank only matters for switchable regions
+    Pc { address: u16, bank: Option<usize> },
+    //Stops on the first instruction of the handler, None for any interrupt
+    Interrupt(Option<u8>),
+    //Stops before an instruction with this (unprefixed) opcode runs
+    Opcode(u8),
+    //Stops after a RET that doesn't match the shadow call stack
+    StackMismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn matches(&self, write: bool) -> bool {
+        match self {
+            Access::Read => !write,
+            Access::Write => write,
+            Access::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub access: Access,
+    //Only trigger when this value is read or written
+    pub value: Option<u8>,
+}
+
+impl Watchpoint {
+    pub fn new(address: u16, access: Access) -> Self {
+        Self {
+            start: address,
+            end: address,
+            access,
+            value: None,
+        }
+    }
+
+    pub fn matches(&self, address: u16, value: u8, write: bool) -> bool {
+        (self.start..=self.end).contains(&address)
+            && self.access.matches(write)
+            && self.value.is_none_or(|expected| expected == value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint {
+        pc: u16,
+    },
+    Watchpoint {
+        address: u16,
+        value: u8,
+        write: bool,
+    },
+    Interrupt {
+        interrupt: u8,
+    },
+    Opcode {
+        pc: u16,
+        opcode: u8,
+    },
+    StackMismatch {
+        mismatch: StackMismatch,
+    },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint { pc } => write!(f, "Breakpoint at ${:04X}", pc),
+            StopReason::Watchpoint {
+                address,
+                value,
+                write: true,
+            } => write!(f, "Write ${:02X} to ${:04X}", value, address),
+            StopReason::Watchpoint { address, value, .. } => {
+                write!(f, "Read ${:02X} from ${:04X}", value, address)
+            }
+            StopReason::Interrupt { interrupt } => write!(f, "Interrupt {}", interrupt),
+            StopReason::Opcode { pc, opcode } => {
+                write!(f, "Opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+            StopReason::StackMismatch { mismatch } => write!(f, "Stack mismatch, {}", mismatch),
+        }
+    }
+}
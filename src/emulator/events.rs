@@ -0,0 +1,225 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use serde::Serialize;
+
+use super::ppu::{Ppu, CYCLES_PER_SCANLINE, LCDC_PPU_ENABLE};
+
+const SCANLINES: u8 = 154;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSource {
+    Ppu,
+    Timer,
+    Joypad,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HardwareEvent {
+    InterruptRequest {
+        interrupt: &'static str,
+        source: EventSource,
+    },
+    InterruptDispatch {
+        interrupt: &'static str,
+    },
+    OamDma {
+        source: u16,
+    },
+    RomBank {
+        bank: usize,
+    },
+    RamBank {
+        bank: usize,
+    },
+    //LCDC, STAT, BGP, OBP0 and OBP1
+    RegisterWrite {
+        register: &'static str,
+        value: u8,
+    },
+    TimerOverflow,
+    HaltEnter,
+    HaltExit,
+}
+
+#[derive(Serialize)]
+struct EventRecord {
+    cycle: u64,
+    ly: u8,
+    dot: u16,
+    #[serde(flatten)]
+    event: HardwareEvent,
+}
+
+//Writes hardware events as JSON lines, timestamped from the start of the current step
+pub struct EventLog {
+    path: String,
+    writer: BufWriter<File>,
+    //First write error, reported by finish
+    error: Option<io::Error>,
+    count: u64,
+
+    cycle: u64,
+    ly: u8,
+    dot: u16,
+    //The PPU stands still while the LCD is off
+    lcd_on: bool,
+}
+
+impl EventLog {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let file = File::create(path).map_err(|err| {
+            io::Error::new(err.kind(), format!("Can't create event log {path}: {err}"))
+        })?;
+
+        Ok(Self {
+            path: path.to_string(),
+            writer: BufWriter::new(file),
+            error: None,
+            count: 0,
+
+            cycle: 0,
+            ly: 0,
+            dot: 0,
+            lcd_on: false,
+        })
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    //Called before every step with the T-cycles run so far
+    pub fn begin_step(&mut self, cycle: u64, ppu: &Ppu) {
+        self.cycle = cycle;
+        self.ly = ppu.ly();
+        self.dot = ppu.dot();
+        self.lcd_on = ppu.get_lcdc(LCDC_PPU_ENABLE);
+    }
+
+    //Event caused by the instruction at the start of the step
+    pub fn record(&mut self, event: HardwareEvent) {
+        self.record_at(0, event);
+    }
+
+    //Event offset T-cycles into the step
+    pub fn record_at(&mut self, offset: i32, event: HardwareEvent) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let (mut ly, mut dot) = (self.ly, self.dot as i32);
+        if self.lcd_on {
+            dot += offset;
+            while dot >= CYCLES_PER_SCANLINE {
+                dot -= CYCLES_PER_SCANLINE;
+                ly = (ly + 1) % SCANLINES;
+            }
+        }
+
+        let record = EventRecord {
+            cycle: self.cycle + offset as u64,
+            ly,
+            dot: dot as u16,
+            event,
+        };
+
+        let result = serde_json::to_writer(&mut self.writer, &record)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+
+        match result {
+            Ok(()) => self.count += 1,
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        let result = match self.error.take() {
+            Some(err) => Err(err),
+            None => self.writer.flush(),
+        };
+
+        result.map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Can't write event log {}: {}", self.path, err),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    //Records the events and returns the log's lines
+    fn log(name: &str, record: impl FnOnce(&mut EventLog)) -> Vec<String> {
+        let path =
+            std::env::temp_dir().join(format!("gameboy-{}-{name}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut log = EventLog::create(path).unwrap();
+        record(&mut log);
+        log.finish().unwrap();
+
+        let text = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn json_lines() {
+        let lines = log("json", |log| {
+            log.begin_step(1234, &Ppu::default());
+            log.record(HardwareEvent::InterruptRequest {
+                interrupt: "vblank",
+                source: EventSource::Ppu,
+            });
+            log.record(HardwareEvent::RegisterWrite {
+                register: "LCDC",
+                value: 0x91,
+            });
+            log.record_at(8, HardwareEvent::TimerOverflow);
+            log.record(HardwareEvent::OamDma { source: 0xC100 });
+            assert_eq!(log.count(), 4);
+        });
+
+        assert_eq!(
+            lines,
+            [
+                r#"{"cycle":1234,"ly":0,"dot":0,"event":"interrupt_request","interrupt":"vblank","source":"ppu"}"#,
+                r#"{"cycle":1234,"ly":0,"dot":0,"event":"register_write","register":"LCDC","value":145}"#,
+                r#"{"cycle":1242,"ly":0,"dot":0,"event":"timer_overflow"}"#,
+                r#"{"cycle":1234,"ly":0,"dot":0,"event":"oam_dma","source":49408}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_wrap_into_the_next_line_and_frame() {
+        let lines = log("wrap", |log| {
+            log.lcd_on = true;
+            log.ly = 10;
+            log.dot = CYCLES_PER_SCANLINE as u16 - 4;
+            log.record_at(4, HardwareEvent::HaltExit);
+
+            log.ly = SCANLINES - 1;
+            log.record_at(8, HardwareEvent::HaltEnter);
+        });
+
+        assert_eq!(
+            lines,
+            [
+                r#"{"cycle":4,"ly":11,"dot":0,"event":"halt_exit"}"#,
+                r#"{"cycle":8,"ly":0,"dot":4,"event":"halt_enter"}"#,
+            ]
+        );
+    }
+}
@@ -1,12 +1,46 @@
-use std::{cell::RefCell, io, rc::Rc};
+use std::{cell::RefCell, fs, io, rc::Rc};
+
+use log::info;
 
 use super::{
-    bus::Bus, cartridge::load_cartridge, cpu::Cpu, interrupt::InterruptState, joypad::Joypad,
-    ppu::Ppu, timer::Timer,
+    boot::{BootSequence, BootStep},
+    bus::Bus,
+    callstack::CallFrame,
+    cartridge::{load_cartridge, CartridgeHeader},
+    cheats::{Cheat, CheatKind, RomPatch},
+    colorization::{self, ColorPalette},
+    cpu::Cpu,
+    debug::{Breakpoint, Location, StopReason, Watchpoint},
+    events::{EventLog, EventSource, HardwareEvent},
+    interrupt::{interrupt_name, InterruptState, INT_TIMER},
+    joypad::Joypad,
+    movie::{BootMode, Movie, MovieMode, MovieSession, MovieStart, MovieStatus},
+    png::write_png,
+    ppu::Ppu,
+    profiler::Profiler,
+    rewind::RewindBuffer,
+    savestate::{invalid, migrate, StateReader, StateWriter, MAGIC, VERSION},
+    search::{Comparison, Operand, RamSearch, ValueType},
+    symbols::Symbols,
+    timer::Timer,
+    trace::{TraceFilter, Tracer},
 };
 
 pub type Shared<T> = Rc<RefCell<T>>;
 
+//T-cycles per second on DMG hardware
+pub const CLOCK_SPEED: u128 = 4194304;
+//One full LCD refresh, 154 scanlines of 456 T-cycles
+pub const CYCLES_PER_FRAME: u128 = 70224;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Model {
+    #[default]
+    Dmg,
+    //NOTE: Only DMG compatibility mode (colorization) is emulated
+    Cgb,
+}
+
 pub struct Gameboy {
     pub cpu: Shared<Cpu>,
     pub ppu: Shared<Ppu>,
@@ -15,6 +49,31 @@ pub struct Gameboy {
     pub joypad: Shared<Joypad>,
     pub bus: Bus,
 
+    pub header: CartridgeHeader,
+    pub model: Model,
+    pub color_palette: ColorPalette,
+
+    //Built-in boot sequence, the CPU sits idle while it runs
+    boot: Option<BootSequence>,
+
+    breakpoints: Vec<Breakpoint>,
+
+    cheats: Vec<Cheat>,
+    //Master switch, individual cheats keep their state while it's off
+    cheats_enabled: bool,
+
+    ram_search: Option<RamSearch>,
+
+    profiler: Option<Profiler>,
+
+    rewind: Option<RewindBuffer>,
+
+    movie: Option<MovieSession>,
+
+    symbols: Symbols,
+
+    //T-cycles since power on
+    cycles: u64,
     pub accum_cycle: u128,
     pub can_render: bool,
 }
@@ -26,8 +85,10 @@ impl Gameboy {
         let timer = Rc::new(RefCell::new(Timer::default()));
         let joypad = Rc::new(RefCell::new(Joypad::default()));
         let interrupt = Rc::new(RefCell::new(InterruptState::default()));
+        let mbc = load_cartridge(path)?;
+        let header = CartridgeHeader::read(mbc.as_ref());
         let bus = Bus::new(
-            load_cartridge(path)?,
+            mbc,
             Rc::clone(&interrupt),
             Rc::clone(&ppu),
             Rc::clone(&timer),
@@ -42,6 +103,28 @@ impl Gameboy {
             joypad,
             bus,
 
+            header,
+            model: Model::Dmg,
+            color_palette: ColorPalette::default(),
+
+            boot: None,
+
+            breakpoints: Vec::new(),
+
+            cheats: Vec::new(),
+            cheats_enabled: true,
+
+            ram_search: None,
+
+            profiler: None,
+
+            rewind: None,
+
+            movie: None,
+
+            symbols: Symbols::default(),
+
+            cycles: 0,
             accum_cycle: 0u128,
             can_render: false,
         })
@@ -52,24 +135,771 @@ impl Gameboy {
         self.ppu.borrow().frame_buffer
     }
 
-    pub fn tick(&mut self) {
-        let cycle = self
-            .cpu
-            .borrow_mut()
-            .step(&mut self.bus, self.interrupt.clone());
+    pub fn get_layer_buffer(&self) -> [u8; 160 * 144] {
+        self.ppu.borrow().layer_buffer
+    }
+
+    //Frame buffer mapped through the active color palette, as 0x00RRGGBB
+    pub fn get_rgb_frame_buffer(&self) -> Vec<u32> {
+        let ppu = self.ppu.borrow();
+        let mut pixels = self
+            .color_palette
+            .apply(&ppu.frame_buffer, &ppu.layer_buffer);
+
+        if ppu.layers.tint {
+            for (pixel, &layer) in pixels.iter_mut().zip(ppu.layer_buffer.iter()) {
+                *pixel = colorization::tint(*pixel, layer);
+            }
+        }
+
+        pixels
+    }
+
+    pub fn save_screenshot(&self, path: &str) -> io::Result<()> {
+        write_png(path, &self.get_rgb_frame_buffer(), 160, 144)
+    }
+
+    //FNV-1a over the shade indices, stable across runs and platforms
+    pub fn frame_hash(&self) -> u64 {
+        self.ppu
+            .borrow()
+            .frame_buffer
+            .iter()
+            .fold(0xCBF29CE484222325, |hash, &pixel| {
+                (hash ^ pixel as u64).wrapping_mul(0x100000001B3)
+            })
+    }
+
+    //Illegal opcode, or HALT with no interrupt enabled to ever wake it up
+    pub fn is_locked_up(&self) -> bool {
+        let cpu = self.cpu.borrow();
+        cpu.locked || (cpu.halt && self.interrupt.borrow().interrupt_enable & 0x1F == 0)
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+
+        self.color_palette = if self.is_colorized() {
+            colorization::compat_palette(&self.header)
+        } else {
+            ColorPalette::default()
+        };
+    }
+
+    //Monochrome cartridge running on a CGB, colors come from the boot rom palettes
+    pub fn is_colorized(&self) -> bool {
+        self.model == Model::Cgb && !self.header.supports_cgb()
+    }
+
+    //Host side palette for monochrome output, ignored while the CGB colorizes the game
+    pub fn set_display_palette(&mut self, palette: ColorPalette) -> bool {
+        if self.is_colorized() {
+            return false;
+        }
+
+        self.color_palette = palette;
+        true
+    }
+
+    //Mimics the CGB boot rom letting the player override the palette with a button combo
+    pub fn select_manual_palette(&mut self) -> bool {
+        if !self.is_colorized() {
+            return false;
+        }
+
+        match colorization::manual_palette(&self.joypad.borrow()) {
+            Some(palette) => {
+                self.color_palette = palette;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //Returns why execution should stop, checked after every instruction
+    pub fn tick(&mut self) -> Option<StopReason> {
+        let logging = self.bus.events.is_some();
+        let halted = self.cpu.borrow().halt;
+        if let Some(events) = &mut self.bus.events {
+            events.begin_step(self.cycles, &self.ppu.borrow());
+        }
 
-        self.timer.borrow_mut().update(cycle, &mut self.bus);
+        let cycle = if self.boot.is_some() {
+            4
+        } else {
+            let mut cpu = self.cpu.borrow_mut();
+            let location = self
+                .profiler
+                .is_some()
+                .then(|| Location::at(&self.bus, cpu.pc));
+
+            let cycle = cpu.step(&mut self.bus, self.interrupt.clone());
+
+            if let (Some(profiler), Some(location)) = (&mut self.profiler, location) {
+                profiler.record(location, cycle, &cpu);
+            }
+            cycle
+        };
+
+        if logging {
+            if self.boot.is_none() {
+                self.record_cpu_events(halted);
+            }
+            self.update_timer_logged(cycle);
+        } else {
+            self.timer.borrow_mut().update(cycle, &mut self.bus);
+        }
         self.ppu.borrow_mut().update(cycle, &mut self.bus);
+        self.record_interrupt_requests(cycle, EventSource::Ppu);
         self.joypad.borrow_mut().update(&mut self.bus);
+        self.record_interrupt_requests(cycle, EventSource::Joypad);
 
+        let frame = self.frame_number();
+        self.cycles += cycle as u64;
         self.accum_cycle += cycle as u128;
 
+        if self.movie.is_some() && self.frame_number() != frame {
+            self.end_movie_frame(frame);
+        }
+
         if self.ppu.borrow().finish_frame {
             self.can_render = true;
             self.ppu.borrow_mut().finish_frame = false;
+            self.update_boot_sequence();
+            self.apply_ram_cheats();
+        }
+
+        if self.boot.is_some() || (self.breakpoints.is_empty() && self.bus.watchpoints.is_empty()) {
+            return None;
+        }
+
+        self.check_breakpoints()
+    }
+
+    //Runs until the end of the frame or until a breakpoint or watchpoint is hit
+    pub fn run_frame(&mut self) -> Option<StopReason> {
+        while self.accum_cycle < CYCLES_PER_FRAME {
+            if let Some(reason) = self.tick() {
+                return Some(reason);
+            }
+        }
+
+        //Overshoot carries into the next frame
+        self.accum_cycle -= CYCLES_PER_FRAME;
+        None
+    }
+
+    fn check_breakpoints(&mut self) -> Option<StopReason> {
+        if let Some(reason) = self.bus.take_watch_hit() {
+            return Some(reason);
+        }
+
+        let cpu = self.cpu.borrow();
+        if let Some(interrupt) = cpu.dispatched_interrupt {
+            let hit = self.breakpoints.iter().any(|breakpoint| {
+                matches!(breakpoint, Breakpoint::Interrupt(kind) if kind.is_none_or(|kind| kind == interrupt))
+            });
+            if hit {
+                return Some(StopReason::Interrupt { interrupt });
+            }
+        }
+
+        if let Some(mismatch) = cpu.stack_mismatch {
+            if self.breakpoints.contains(&Breakpoint::StackMismatch) {
+                return Some(StopReason::StackMismatch { mismatch });
+            }
+        }
+
+        //Nothing new runs while halted
+        if cpu.halt || cpu.locked {
+            return None;
+        }
+
+        let pc = cpu.pc;
+        let opcode = self.bus.peek_byte(pc);
+        let bank = self.bus.bank_at(pc);
+
+        self.breakpoints
+            .iter()
+            .find_map(|breakpoint| match *breakpoint {
+                Breakpoint::Pc {
+                    address,
+                    bank: wanted,
+                } if address == pc && (wanted.is_none() || wanted == bank) => {
+                    Some(StopReason::Breakpoint { pc })
+                }
+                Breakpoint::Opcode(wanted) if wanted == opcode => {
+                    Some(StopReason::Opcode { pc, opcode })
+                }
+                _ => None,
+            })
+    }
+
+    //Labels from the rom's .sym/.map files, empty when there are none
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    //Innermost call last
+    pub fn call_stack(&self) -> Vec<CallFrame> {
+        self.cpu.borrow().call_stack.frames().to_vec()
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|existing| *existing != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.bus.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.bus.watchpoints.contains(&watchpoint) {
+            self.bus.watchpoints.push(watchpoint);
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let count = self.bus.watchpoints.len();
+        self.bus
+            .watchpoints
+            .retain(|existing| *existing != watchpoint);
+        self.bus.watchpoints.len() != count
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.bus.watchpoints.clear();
+        self.bus.take_watch_hit();
+    }
+
+    fn active_cheats(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats
+            .iter()
+            .filter(|cheat| self.cheats_enabled && cheat.enabled)
+    }
+
+    //GameShark codes hold their value by rewriting it every frame
+    fn apply_ram_cheats(&mut self) {
+        let writes: Vec<(u16, u8)> = self
+            .active_cheats()
+            .filter_map(|cheat| match cheat.kind {
+                CheatKind::GameShark { address, value } => Some((address, value)),
+                CheatKind::GameGenie { .. } => None,
+            })
+            .collect();
+
+        for (address, value) in writes {
+            self.bus.poke_byte(address, value);
+        }
+    }
+
+    fn update_rom_patches(&mut self) {
+        let patches = self
+            .active_cheats()
+            .filter_map(|cheat| match cheat.kind {
+                CheatKind::GameGenie {
+                    address,
+                    value,
+                    compare,
+                } => Some(RomPatch {
+                    address,
+                    value,
+                    compare,
+                }),
+                CheatKind::GameShark { .. } => None,
+            })
+            .collect();
+
+        self.bus.set_rom_patches(patches);
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) {
+        self.cheats = cheats;
+        self.update_rom_patches();
+    }
+
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+        self.update_rom_patches();
+    }
+
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.get_mut(index) else {
+            return false;
+        };
+
+        cheat.enabled = enabled;
+        self.update_rom_patches();
+        true
+    }
+
+    pub fn cheats_enabled(&self) -> bool {
+        self.cheats_enabled
+    }
+
+    pub fn set_cheats_enabled(&mut self, enabled: bool) {
+        self.cheats_enabled = enabled;
+        self.update_rom_patches();
+    }
+
+    //Snapshots RAM, returns the number of candidates
+    pub fn start_ram_search(&mut self, value_type: ValueType) -> usize {
+        let search = RamSearch::new(&self.bus, value_type);
+        let count = search.candidates().len();
+        self.ram_search = Some(search);
+        count
+    }
+
+    //None when no search was started
+    pub fn filter_ram_search(&mut self, comparison: Comparison, operand: Operand) -> Option<usize> {
+        let search = self.ram_search.as_mut()?;
+        Some(search.filter(&self.bus, comparison, operand))
+    }
+
+    pub fn ram_search(&self) -> Option<&RamSearch> {
+        self.ram_search.as_ref()
+    }
+
+    pub fn clear_ram_search(&mut self) {
+        self.ram_search = None;
+    }
+
+    //Charges every following step to its routine, see Profiler
+    pub fn start_profiler(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn stop_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    //Whole machine as a versioned binary blob, see savestate
+    pub fn save_state(&self) -> io::Result<Vec<u8>> {
+        if self.boot.is_some() {
+            return Err(invalid("Can't save while the boot sequence is running"));
+        }
+
+        let mut state = StateWriter::new();
+        state.bytes(MAGIC);
+        state.u16(VERSION);
+        state.bytes(&self.header.title);
+        state.u8(self.header.header_checksum);
+        state.u8(self.model as u8);
+        self.write_state(&mut state);
+
+        Ok(state.into_bytes())
+    }
+
+    //Leaves the machine untouched when the state can't be loaded
+    pub fn load_state(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut state = StateReader::new(data);
+
+        if state.bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(invalid("Not a save state"));
+        }
+        let version = state.u16()?;
+
+        let title: [u8; 16] = state.array()?;
+        let checksum = state.u8()?;
+        if title != self.header.title || checksum != self.header.header_checksum {
+            return Err(invalid(format!(
+                "Save state is for another game ({})",
+                String::from_utf8_lossy(&title).trim_end_matches('\0')
+            )));
+        }
+
+        let model = state.u8()?;
+        if model != self.model as u8 {
+            return Err(invalid(format!(
+                "Save state was made with --model {}",
+                if model == Model::Cgb as u8 {
+                    "cgb"
+                } else {
+                    "dmg"
+                }
+            )));
+        }
+
+        let body = migrate(version, state.rest())?;
+
+        //Body starts with the cycle count
+        let frame = StateReader::new(&body).u64()? / CYCLES_PER_FRAME as u64;
+        if let Some(movie) = &self.movie {
+            movie.check_seek(frame)?;
+        }
+
+        let mut backup = StateWriter::new();
+        self.write_state(&mut backup);
+
+        if let Err(err) = self.read_state(&mut StateReader::new(&body)) {
+            self.read_state(&mut StateReader::new(&backup.into_bytes()))
+                .expect("the machine's own state loads back");
+            return Err(err);
+        }
+
+        self.boot = None;
+        self.can_render = true;
+
+        if let Some(buttons) = self.movie.as_mut().and_then(|movie| movie.seek(frame)) {
+            self.joypad.borrow_mut().set_buttons(buttons);
+        }
+        Ok(())
+    }
+
+    pub fn save_state_file(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.save_state()?).map_err(|err| {
+            io::Error::new(err.kind(), format!("Can't write save state {path}: {err}"))
+        })
+    }
+
+    pub fn load_state_file(&mut self, path: &str) -> io::Result<()> {
+        let data = fs::read(path).map_err(|err| {
+            io::Error::new(err.kind(), format!("Can't read save state {path}: {err}"))
+        })?;
+        self.load_state(&data)
+    }
+
+    fn write_state(&self, state: &mut StateWriter) {
+        state.u64(self.cycles);
+        state.u64(self.accum_cycle as u64);
+
+        state.section(b"CPU ", &*self.cpu.borrow());
+        state.section(b"INT ", &*self.interrupt.borrow());
+        state.section(b"TIMR", &*self.timer.borrow());
+        state.section(b"PPU ", &*self.ppu.borrow());
+        state.section(b"JOYP", &*self.joypad.borrow());
+        state.section(b"BUS ", &self.bus);
+        state.section(b"MBC ", self.bus.mbc());
+    }
+
+    fn read_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        self.cycles = state.u64()?;
+        self.accum_cycle = state.u64()? as u128;
+
+        state.section(b"CPU ", &mut *self.cpu.borrow_mut())?;
+        state.section(b"INT ", &mut *self.interrupt.borrow_mut())?;
+        state.section(b"TIMR", &mut *self.timer.borrow_mut())?;
+        state.section(b"PPU ", &mut *self.ppu.borrow_mut())?;
+        state.section(b"JOYP", &mut *self.joypad.borrow_mut())?;
+        state.section(b"BUS ", &mut self.bus)?;
+        state.section(b"MBC ", self.bus.mbc_mut())?;
+
+        if !state.is_empty() {
+            return Err(invalid("Save state has trailing data"));
+        }
+        Ok(())
+    }
+
+    //Keeps a snapshot every interval frames in at most budget bytes, see RewindBuffer
+    pub fn start_rewind(&mut self, interval: u32, budget: usize) {
+        self.rewind = Some(RewindBuffer::new(interval, budget));
+    }
+
+    pub fn stop_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    pub fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    //Called by the frontend after every completed frame
+    pub fn record_rewind_frame(&mut self) {
+        if !self.rewind.as_mut().is_some_and(RewindBuffer::frame_done) {
+            return;
+        }
+
+        //Nothing to snapshot during the boot sequence
+        if let Ok(state) = self.save_state() {
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
+        }
+    }
+
+    //Goes back to the last snapshot, one rewind interval. False when there is none
+    pub fn rewind(&mut self) -> io::Result<bool> {
+        let Some(state) = self.rewind.as_mut().and_then(RewindBuffer::pop) else {
+            return Ok(false);
+        };
+
+        self.load_state(&state)?;
+        Ok(true)
+    }
+
+    //Frames completed since power on, frames end every CYCLES_PER_FRAME T-cycles
+    pub fn frame_number(&self) -> u64 {
+        self.cycles / CYCLES_PER_FRAME as u64
+    }
+
+    fn boot_mode(&self) -> BootMode {
+        if self.boot.is_some() {
+            BootMode::Builtin
+        } else if self.bus.bootrom_enabled() {
+            BootMode::Bootrom
+        } else {
+            BootMode::NoBootrom
+        }
+    }
+
+    //Records the joypad from power on when nothing ran yet, otherwise from a save state of now
+    pub fn record_movie(&mut self) -> io::Result<()> {
+        let start = if self.cycles == 0 {
+            MovieStart::PowerOn(self.boot_mode())
+        } else {
+            MovieStart::State(self.save_state()?)
+        };
+
+        self.movie = Some(MovieSession {
+            movie: Movie::new(&self.header, self.model, start),
+            mode: MovieMode::Recording,
+            start_frame: self.frame_number(),
+            desync: None,
+        });
+        Ok(())
+    }
+
+    //Power on movies need a game that hasn't run yet, started the way the movie was
+    pub fn play_movie(&mut self, movie: Movie, read_only: bool) -> io::Result<()> {
+        movie.check(&self.header, self.model)?;
+
+        self.movie = None;
+        match &movie.start {
+            MovieStart::PowerOn(_) if self.cycles != 0 => {
+                return Err(invalid(
+                    "Movies recorded from power on have to be played on a game that hasn't started",
+                ));
+            }
+            MovieStart::PowerOn(_) => movie.check_boot(self.boot_mode())?,
+            MovieStart::State(state) => self.load_state(state)?,
+        }
+
+        let mode = match (movie.frames.first(), read_only) {
+            (Some(frame), _) => {
+                self.joypad.borrow_mut().set_buttons(frame.buttons);
+                MovieMode::Playing { read_only }
+            }
+            (None, true) => MovieMode::Finished,
+            (None, false) => MovieMode::Recording,
+        };
+
+        self.movie = Some(MovieSession {
+            movie,
+            mode,
+            start_frame: self.frame_number(),
+            desync: None,
+        });
+        Ok(())
+    }
+
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        self.movie.take().map(|session| session.movie)
+    }
+
+    pub fn movie_status(&self) -> Option<MovieStatus> {
+        self.movie
+            .as_ref()
+            .map(|movie| movie.status(self.frame_number()))
+    }
+
+    //The joypad belongs to the movie while it plays
+    pub fn is_playing_movie(&self) -> bool {
+        self.movie
+            .as_ref()
+            .is_some_and(|movie| matches!(movie.mode, MovieMode::Playing { .. }))
+    }
+
+    fn end_movie_frame(&mut self, frame: u64) {
+        let buttons = self.joypad.borrow().buttons();
+        let hash = self.frame_hash();
+
+        let Some(movie) = &mut self.movie else {
+            return;
+        };
+        if let Some(buttons) = movie.end_frame(frame, buttons, hash) {
+            self.joypad.borrow_mut().set_buttons(buttons);
+        }
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    //Logs hardware events to path as JSON lines, see EventLog
+    pub fn start_event_log(&mut self, path: &str) -> io::Result<()> {
+        self.bus.events = Some(EventLog::create(path)?);
+        self.bus.take_requested_interrupts();
+        Ok(())
+    }
+
+    pub fn stop_event_log(&mut self) -> io::Result<()> {
+        match self.bus.events.take() {
+            Some(events) => {
+                let count = events.count();
+                events.finish()?;
+                info!(target: "Events", "Logged {} hardware events", count);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn record_cpu_events(&mut self, halted: bool) {
+        let cpu = self.cpu.borrow();
+        let Some(events) = &mut self.bus.events else {
+            return;
+        };
+
+        if let Some(interrupt) = cpu.dispatched_interrupt {
+            events.record(HardwareEvent::InterruptDispatch {
+                interrupt: interrupt_name(interrupt),
+            });
+        }
+
+        match (halted, cpu.halt) {
+            (false, true) => events.record(HardwareEvent::HaltEnter),
+            (true, false) => events.record(HardwareEvent::HaltExit),
+            _ => (),
+        }
+    }
+
+    //Steps the timer one T-cycle at a time so overflows get their exact cycle
+    fn update_timer_logged(&mut self, cycle: i32) {
+        let timer = Rc::clone(&self.timer);
+
+        for offset in 0..cycle {
+            if timer.borrow_mut().step(&mut self.bus) {
+                self.bus.take_requested_interrupts();
+                if let Some(events) = &mut self.bus.events {
+                    events.record_at(offset, HardwareEvent::TimerOverflow);
+                    events.record_at(
+                        offset,
+                        HardwareEvent::InterruptRequest {
+                            interrupt: interrupt_name(INT_TIMER),
+                            source: EventSource::Timer,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn record_interrupt_requests(&mut self, offset: i32, source: EventSource) {
+        if self.bus.events.is_none() {
+            return;
+        }
+
+        let requested = self.bus.take_requested_interrupts();
+        let Some(events) = &mut self.bus.events else {
+            return;
+        };
+
+        for interrupt in (0..5).filter(|interrupt| requested & (1 << interrupt) != 0) {
+            events.record_at(
+                offset,
+                HardwareEvent::InterruptRequest {
+                    interrupt: interrupt_name(interrupt),
+                    source,
+                },
+            );
+        }
+    }
+
+    //Shows the cartridge logo and checks the header before handing over, like the boot rom
+    pub fn start_boot_sequence(&mut self) {
+        self.boot = Some(BootSequence::new(&mut self.bus));
+    }
+
+    pub fn is_booting(&self) -> bool {
+        self.boot.is_some()
+    }
+
+    fn update_boot_sequence(&mut self) {
+        let Some(boot) = &mut self.boot else {
+            return;
+        };
+
+        match boot.next_frame(&mut self.bus) {
+            BootStep::Running => (),
+            BootStep::Done => {
+                self.boot = None;
+                self.no_bootrom_init();
+
+                //The boot rom leaves H and C set unless the header checksum is 0
+                if self.header.header_checksum == 0 {
+                    self.cpu.borrow_mut().f = 0x80;
+                }
+            }
+            BootStep::LockUp => {
+                self.boot = None;
+                self.cpu.borrow_mut().locked = true;
+            }
+        }
+    }
+
+    //Logs every executed instruction to path, see Tracer
+    pub fn start_trace(&mut self, path: &str, filter: TraceFilter, labels: bool) -> io::Result<()> {
+        let mut tracer = Tracer::create(path, filter)?;
+        if labels {
+            tracer = tracer.with_labels(self.symbols.clone());
+        }
+
+        self.cpu.borrow_mut().tracer = Some(tracer);
+        Ok(())
+    }
+
+    //Trace and event log keep writing to the same files after a reset
+    pub fn take_logs(&mut self, old: &mut Gameboy) {
+        self.cpu.borrow_mut().tracer = old.cpu.borrow_mut().tracer.take();
+        self.bus.events = old.bus.events.take();
+        self.bus.take_requested_interrupts();
+    }
+
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        match self.cpu.borrow_mut().tracer.take() {
+            Some(tracer) => {
+                let lines = tracer.lines();
+                tracer.finish()?;
+                info!(target: "Trace", "Traced {} instructions", lines);
+                Ok(())
+            }
+            None => Ok(()),
         }
     }
 
+    //Execution starts at 0x0000 inside the boot rom, which hands over to the cartridge at 0x0100
+    pub fn load_bootrom(&mut self, path: &str) -> io::Result<()> {
+        self.bus.load_bootrom(path)
+    }
+
     pub fn no_bootrom_init(&mut self) {
         let mut cpu = self.cpu.borrow_mut();
 
@@ -115,3 +945,51 @@ impl Gameboy {
         self.bus.write_byte(0xFF50, 1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Runs the built-in boot sequence on a blank 32 KiB cartridge
+    fn boot(name: &str, title: u8, header_checksum: u8) -> Gameboy {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x134] = title;
+        rom[0x14D] = header_checksum;
+
+        let path = std::env::temp_dir().join(format!("gameboy-{}-{name}.gb", std::process::id()));
+        fs::write(&path, rom).unwrap();
+        let mut gameboy = Gameboy::new(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        gameboy.start_boot_sequence();
+        while gameboy.is_booting() {
+            assert!(gameboy.frame_number() < 600, "Boot sequence never finished");
+            gameboy.tick();
+        }
+
+        gameboy
+    }
+
+    #[test]
+    fn builtin_boot_sets_h_and_c() {
+        //0xE7 is the checksum of an all zero header
+        let gameboy = boot("nonzero", 0, 0xE7);
+        let cpu = gameboy.cpu.borrow();
+        assert_eq!((cpu.a, cpu.f, cpu.pc), (0x01, 0xB0, 0x100));
+        assert!(!cpu.locked);
+    }
+
+    #[test]
+    fn builtin_boot_clears_h_and_c_on_a_zero_checksum() {
+        //A 0xE7 title byte brings the checksum down to 0
+        let gameboy = boot("zero", 0xE7, 0x00);
+        let cpu = gameboy.cpu.borrow();
+        assert_eq!((cpu.a, cpu.f, cpu.pc), (0x01, 0x80, 0x100));
+    }
+
+    #[test]
+    fn builtin_boot_locks_up_on_a_bad_checksum() {
+        let gameboy = boot("bad", 0, 0x12);
+        assert!(gameboy.cpu.borrow().locked);
+    }
+}
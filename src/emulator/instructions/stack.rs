@@ -1,7 +1,25 @@
-use crate::emulator::{bus::Bus, cpu::Cpu, gameboy::Shared, interrupt::InterruptState};
+use crate::emulator::{
+    bus::Bus,
+    callstack::{CallFrame, CallKind},
+    cpu::Cpu,
+    debug::Location,
+    gameboy::Shared,
+    interrupt::InterruptState,
+};
 
 use super::operand::Operands;
 
+//Shadow call stack entry for a call that just pushed cpu.pc
+pub fn push_frame(cpu: &mut Cpu, bus: &Bus, kind: CallKind, target: u16) {
+    let frame = CallFrame {
+        kind,
+        target: Location::at(bus, target),
+        return_to: Location::at(bus, cpu.pc),
+        sp: cpu.sp,
+    };
+    cpu.call_stack.push(frame);
+}
+
 pub fn push(cpu: &mut Cpu, bus: &mut Bus, operand: Operands) {
     let src: u16 = match operand {
         Operands::AF => cpu.af(),
@@ -49,6 +67,7 @@ pub fn call(cpu: &mut Cpu, bus: &mut Bus) {
 
     let new_address = cpu.next_short(bus);
 
+    push_frame(cpu, bus, CallKind::Call, new_address);
     cpu.pc = new_address;
 }
 
@@ -77,6 +96,7 @@ pub fn call_cc(cpu: &mut Cpu, bus: &mut Bus, operand: Operands) -> bool {
     cpu.sp -= 1;
     bus.write_byte(cpu.sp, lo_byte);
 
+    push_frame(cpu, bus, CallKind::Call, new_address);
     cpu.pc = new_address;
 
     true
@@ -89,6 +109,7 @@ pub fn ret(cpu: &mut Cpu, bus: &mut Bus) {
     cpu.sp += 1;
 
     cpu.pc = (hi << 8) | lo;
+    cpu.stack_mismatch = cpu.call_stack.pop(cpu.pc, cpu.sp);
 }
 
 pub fn reti(cpu: &mut Cpu, bus: &mut Bus, interrupt: Shared<InterruptState>) -> bool {
@@ -133,6 +154,7 @@ pub fn rst(cpu: &mut Cpu, bus: &mut Bus, operand: Operands) -> bool {
     cpu.sp = cpu.sp.wrapping_sub(1);
     bus.write_byte(cpu.sp, (cpu.pc & 0xFF) as u8);
 
+    push_frame(cpu, bus, CallKind::Rst, address);
     cpu.pc = address;
     false
 }
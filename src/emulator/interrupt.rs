@@ -1,3 +1,7 @@
+use std::io;
+
+use super::savestate::{SaveState, StateReader, StateWriter};
+
 pub const ADDRESS_IE: u16 = 0xFFFF;
 pub const ADDRESS_IF: u16 = 0xFF0F;
 
@@ -7,6 +11,17 @@ pub const INT_TIMER: u8 = 2;
 pub const INT_LCD: u8 = 1;
 pub const INT_VBLANK: u8 = 0;
 
+pub fn interrupt_name(interrupt: u8) -> &'static str {
+    match interrupt {
+        INT_VBLANK => "vblank",
+        INT_LCD => "stat",
+        INT_TIMER => "timer",
+        INT_SERIAL => "serial",
+        INT_JOYPAD => "joypad",
+        _ => "unknown",
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct InterruptState {
     pub ime: bool,
@@ -23,3 +38,18 @@ impl InterruptState {
         self.interrupt_flag & self.interrupt_enable != 0
     }
 }
+
+impl SaveState for InterruptState {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.ime);
+        state.u8(self.interrupt_enable);
+        state.u8(self.interrupt_flag);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        self.ime = state.bool()?;
+        self.interrupt_enable = state.u8()?;
+        self.interrupt_flag = state.u8()?;
+        Ok(())
+    }
+}
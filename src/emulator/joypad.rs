@@ -1,4 +1,10 @@
-use super::{bus::Bus, interrupt::INT_JOYPAD};
+use std::io;
+
+use super::{
+    bus::Bus,
+    interrupt::INT_JOYPAD,
+    savestate::{SaveState, StateReader, StateWriter},
+};
 
 pub const JOYPAD: u16 = 0xFF00;
 
@@ -17,7 +23,45 @@ pub struct Joypad {
     joypad_byte: u8,
 }
 
+//Pressed buttons as a bitmask, in the order the joypad register lists them
+pub const BUTTON_A: u8 = 1 << 0;
+pub const BUTTON_B: u8 = 1 << 1;
+pub const BUTTON_SELECT: u8 = 1 << 2;
+pub const BUTTON_START: u8 = 1 << 3;
+pub const BUTTON_RIGHT: u8 = 1 << 4;
+pub const BUTTON_LEFT: u8 = 1 << 5;
+pub const BUTTON_UP: u8 = 1 << 6;
+pub const BUTTON_DOWN: u8 = 1 << 7;
+
 impl Joypad {
+    //Button fields are active low
+    pub fn buttons(&self) -> u8 {
+        [
+            (self.btn_a, BUTTON_A),
+            (self.btn_b, BUTTON_B),
+            (self.select, BUTTON_SELECT),
+            (self.start, BUTTON_START),
+            (self.right, BUTTON_RIGHT),
+            (self.left, BUTTON_LEFT),
+            (self.up, BUTTON_UP),
+            (self.down, BUTTON_DOWN),
+        ]
+        .iter()
+        .filter(|(released, _)| !released)
+        .fold(0, |buttons, (_, button)| buttons | button)
+    }
+
+    pub fn set_buttons(&mut self, buttons: u8) {
+        self.btn_a = buttons & BUTTON_A == 0;
+        self.btn_b = buttons & BUTTON_B == 0;
+        self.select = buttons & BUTTON_SELECT == 0;
+        self.start = buttons & BUTTON_START == 0;
+        self.right = buttons & BUTTON_RIGHT == 0;
+        self.left = buttons & BUTTON_LEFT == 0;
+        self.up = buttons & BUTTON_UP == 0;
+        self.down = buttons & BUTTON_DOWN == 0;
+    }
+
     pub fn update(&mut self, bus: &mut Bus) {
         let is_button = (self.joypad_byte >> 5) & 1;
         let is_direction = (self.joypad_byte >> 4) & 1;
@@ -46,3 +90,17 @@ impl Joypad {
         self.joypad_byte |= value & mask;
     }
 }
+
+//Buttons are host input, they are read again on the next frame
+impl SaveState for Joypad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.old_joypad_byte);
+        state.u8(self.joypad_byte);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        self.old_joypad_byte = state.u8()?;
+        self.joypad_byte = state.u8()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,16 @@
-pub trait MBC {
+use crate::emulator::savestate::SaveState;
+
+//Bank registers and cartridge RAM go into save states
+pub trait MBC: SaveState {
     fn read_byte(&self, address: u16) -> u8;
     fn write_byte(&mut self, address: u16, value: u8);
+
+    //Banks mapped at 0x4000-0x7FFF and 0xA000-0xBFFF, used by the debugger
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
+    fn ram_bank(&self) -> usize {
+        0
+    }
 }
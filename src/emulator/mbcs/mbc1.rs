@@ -1,6 +1,9 @@
+use std::io;
+
 use log::{info, warn};
 
 use super::mbc::MBC;
+use crate::emulator::savestate::{invalid, SaveState, StateReader, StateWriter};
 
 pub struct MBC1 {
     rom_bank: u8,
@@ -84,6 +87,14 @@ impl MBC1 {
 }
 
 impl MBC for MBC1 {
+    fn rom_bank(&self) -> usize {
+        self.rom_bank as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        self.ram_bank as usize
+    }
+
     fn read_byte(&self, address: u16) -> u8 {
         // debug!(target: "Memory", "Reading MBC at {:04X}", address);
 
@@ -112,3 +123,35 @@ impl MBC for MBC1 {
         self.ram[local_address + (self.ram_bank as u16 * 0x2000) as usize] = value;
     }
 }
+
+impl SaveState for MBC1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.rom_bank);
+        state.u8(self.ram_bank);
+        state.bool(self.rom_banking);
+        state.bool(self.ram_enable);
+        state.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        let rom_bank = state.u8()?;
+        if (rom_bank as usize + 1) * 0x4000 > self.rom.len() {
+            return Err(invalid(format!(
+                "ROM bank {} is past the end of the {} KiB ROM",
+                rom_bank,
+                self.rom.len() / 1024
+            )));
+        }
+
+        let ram_bank = state.u8()?;
+        if ram_bank as usize * 0x2000 >= self.ram.len() {
+            return Err(invalid(format!("Invalid RAM bank {}", ram_bank)));
+        }
+
+        self.rom_bank = rom_bank;
+        self.ram_bank = ram_bank;
+        self.rom_banking = state.bool()?;
+        self.ram_enable = state.bool()?;
+        state.fill(&mut self.ram)
+    }
+}
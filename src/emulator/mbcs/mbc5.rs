@@ -1,4 +1,7 @@
+use std::io;
+
 use super::mbc::MBC;
+use crate::emulator::savestate::{invalid, SaveState, StateReader, StateWriter};
 
 pub struct MBC5 {
     //NOTE: Unsigned 9-bit
@@ -43,6 +46,14 @@ impl MBC5 {
 }
 
 impl MBC for MBC5 {
+    fn rom_bank(&self) -> usize {
+        self.rom_bank as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        self.ram_bank as usize
+    }
+
     fn read_byte(&self, address: u16) -> u8 {
         match address {
             0x0000..=0x3FFF => self.rom[address as usize],
@@ -52,7 +63,7 @@ impl MBC for MBC5 {
                 self.rom[local_address]
             }
             0xA000..=0xBFFF => {
-                let local_address = (0x2000 * (self.ram_bank as u16) + (address - 0xA000)) as usize;
+                let local_address = 0x2000 * self.ram_bank as usize + (address - 0xA000) as usize;
                 self.ram[local_address]
             }
             _ => 0,
@@ -66,10 +77,40 @@ impl MBC for MBC5 {
             0x3000..=0x3FFF => self.change_rom_bank_hi(value),
             0x4000..=0x4FFF => self.change_ram_bank(value),
             0xA000..=0xBFFF => {
-                let local_address = (0x2000 * (self.ram_bank as u16) + (address - 0xA000)) as usize;
+                let local_address = 0x2000 * self.ram_bank as usize + (address - 0xA000) as usize;
                 self.ram[local_address] = value;
             }
             _ => (),
         }
     }
 }
+
+impl SaveState for MBC5 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.rom_bank);
+        state.u8(self.ram_bank);
+        state.bool(self.ram_enable);
+        state.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        let rom_bank = state.u16()?;
+        if (rom_bank as usize + 1) * 0x4000 > self.rom.len() {
+            return Err(invalid(format!(
+                "ROM bank {} is past the end of the {} KiB ROM",
+                rom_bank,
+                self.rom.len() / 1024
+            )));
+        }
+
+        let ram_bank = state.u8()?;
+        if ram_bank as usize * 0x2000 >= self.ram.len() {
+            return Err(invalid(format!("Invalid RAM bank {}", ram_bank)));
+        }
+
+        self.rom_bank = rom_bank;
+        self.ram_bank = ram_bank;
+        self.ram_enable = state.bool()?;
+        state.fill(&mut self.ram)
+    }
+}
@@ -1,4 +1,7 @@
+use std::io;
+
 use super::mbc::MBC;
+use crate::emulator::savestate::{SaveState, StateReader, StateWriter};
 
 pub struct Rom {
     memory: [u8; 0x8000],
@@ -15,10 +18,23 @@ impl Rom {
 
 impl MBC for Rom {
     fn read_byte(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+        match address {
+            0x0000..=0x7FFF => self.memory[address as usize],
+            //No cartridge RAM, the bus floats
+            _ => 0xFF,
+        }
     }
 
     fn write_byte(&mut self, _address: u16, _value: u8) {
         // self.memory[address as usize] = value;
     }
 }
+
+//Nothing to switch and no RAM
+impl SaveState for Rom {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,11 +1,26 @@
+pub mod boot;
 pub mod bus;
+pub mod callstack;
 pub mod cartridge;
+pub mod cheats;
+pub mod colorization;
 pub mod cpu;
+pub mod debug;
+pub mod events;
 pub mod gameboy;
 pub mod instructions;
 pub mod interrupt;
 pub mod joypad;
 pub mod mbcs;
+pub mod movie;
 // pub mod memory;
+pub mod png;
 pub mod ppu;
+pub mod profiler;
+pub mod rewind;
+pub mod savestate;
+pub mod search;
+pub mod symbols;
 pub mod timer;
+pub mod trace;
+pub mod vram;
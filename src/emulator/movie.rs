@@ -0,0 +1,493 @@
+use std::{fs, io};
+
+use super::{
+    cartridge::CartridgeHeader,
+    gameboy::Model,
+    savestate::{invalid, StateReader, StateWriter},
+};
+
+pub const MAGIC: &[u8; 8] = b"GBRSMOVI";
+pub const VERSION: u16 = 1;
+
+//How the machine was started before the first recorded frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootMode {
+    NoBootrom,
+    Builtin,
+    Bootrom,
+}
+
+impl BootMode {
+    fn flag(self) -> &'static str {
+        match self {
+            BootMode::NoBootrom => "neither --bootrom nor --builtin-boot",
+            BootMode::Builtin => "--builtin-boot",
+            BootMode::Bootrom => "--bootrom",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    PowerOn(BootMode),
+    //Save state, see Gameboy::save_state
+    State(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    //Pressed buttons, see joypad::BUTTON_A
+    pub buttons: u8,
+    //Gameboy::frame_hash at the end of the frame
+    pub hash: u64,
+}
+
+//Joypad input for every frame since power on or a save state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub title: [u8; 16],
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub model: Model,
+    pub start: MovieStart,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(header: &CartridgeHeader, model: Model, start: MovieStart) -> Self {
+        Self {
+            title: header.title,
+            header_checksum: header.header_checksum,
+            global_checksum: header.global_checksum,
+            model,
+            start,
+            frames: Vec::new(),
+        }
+    }
+
+    //Same cartridge, model and boot as the running game
+    pub fn check(&self, header: &CartridgeHeader, model: Model) -> io::Result<()> {
+        if self.title != header.title
+            || self.header_checksum != header.header_checksum
+            || self.global_checksum != header.global_checksum
+        {
+            return Err(invalid(format!(
+                "Movie is for another rom ({}, checksum {:04X})",
+                String::from_utf8_lossy(&self.title).trim_end_matches('\0'),
+                self.global_checksum
+            )));
+        }
+
+        if self.model != model {
+            return Err(invalid(format!(
+                "Movie was recorded with --model {}",
+                model_name(self.model)
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn check_boot(&self, boot: BootMode) -> io::Result<()> {
+        match self.start {
+            MovieStart::PowerOn(recorded) if recorded != boot => Err(invalid(format!(
+                "Movie was recorded with {}",
+                recorded.flag()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut movie = StateWriter::new();
+        movie.bytes(MAGIC);
+        movie.u16(VERSION);
+        movie.bytes(&self.title);
+        movie.u8(self.header_checksum);
+        movie.u16(self.global_checksum);
+        movie.u8(self.model as u8);
+
+        match &self.start {
+            MovieStart::PowerOn(boot) => {
+                movie.u8(0);
+                movie.u8(*boot as u8);
+            }
+            MovieStart::State(state) => {
+                movie.u8(1);
+                movie.u32(state.len() as u32);
+                movie.bytes(state);
+            }
+        }
+
+        movie.u32(self.frames.len() as u32);
+        for frame in &self.frames {
+            movie.u8(frame.buttons);
+            movie.u64(frame.hash);
+        }
+
+        movie.into_bytes()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let mut movie = StateReader::new(data);
+
+        if movie.bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(invalid("Not a movie"));
+        }
+        let version = movie.u16()?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "Movie is version {}, this build only reads version {}",
+                version, VERSION
+            )));
+        }
+
+        let title = movie.array()?;
+        let header_checksum = movie.u8()?;
+        let global_checksum = movie.u16()?;
+        let model = match movie.u8()? {
+            0 => Model::Dmg,
+            1 => Model::Cgb,
+            model => return Err(invalid(format!("Invalid model {}", model))),
+        };
+
+        let start = match movie.u8()? {
+            0 => MovieStart::PowerOn(match movie.u8()? {
+                0 => BootMode::NoBootrom,
+                1 => BootMode::Builtin,
+                2 => BootMode::Bootrom,
+                boot => return Err(invalid(format!("Invalid boot mode {}", boot))),
+            }),
+            1 => {
+                let length = movie.u32()? as usize;
+                MovieStart::State(movie.bytes(length)?.to_vec())
+            }
+            start => return Err(invalid(format!("Invalid movie start {}", start))),
+        };
+
+        let count = movie.u32()? as usize;
+        let mut frames = Vec::with_capacity(count.min(data.len() / 9));
+        for _ in 0..count {
+            frames.push(MovieFrame {
+                buttons: movie.u8()?,
+                hash: movie.u64()?,
+            });
+        }
+
+        if !movie.is_empty() {
+            return Err(invalid("Movie has trailing data"));
+        }
+
+        Ok(Self {
+            title,
+            header_checksum,
+            global_checksum,
+            model,
+            start,
+            frames,
+        })
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let data = fs::read(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("Can't read movie {path}: {err}")))?;
+        Self::from_bytes(&data).map_err(|err| io::Error::new(err.kind(), format!("{path}: {err}")))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+            .map_err(|err| io::Error::new(err.kind(), format!("Can't write movie {path}: {err}")))
+    }
+}
+
+fn model_name(model: Model) -> &'static str {
+    match model {
+        Model::Dmg => "dmg",
+        Model::Cgb => "cgb",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieMode {
+    Recording,
+    //Read-only keeps playing when a state is loaded, read-write records from there
+    Playing { read_only: bool },
+    //Read-only playback ran out of frames
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieStatus {
+    pub mode: MovieMode,
+    //Frames since the start of the movie
+    pub frame: u64,
+    pub length: u64,
+    //First frame whose hash didn't match the recording
+    pub desync: Option<u64>,
+}
+
+//A movie being recorded or played back, driven by Gameboy::tick at every frame boundary
+#[derive(Debug, Clone)]
+pub struct MovieSession {
+    pub movie: Movie,
+    pub mode: MovieMode,
+    //Absolute frame number the movie starts at, see Gameboy::frame_number
+    pub start_frame: u64,
+    pub desync: Option<u64>,
+}
+
+impl MovieSession {
+    pub fn status(&self, frame_number: u64) -> MovieStatus {
+        MovieStatus {
+            mode: self.mode,
+            frame: frame_number.saturating_sub(self.start_frame),
+            length: self.movie.frames.len() as u64,
+            desync: self.desync,
+        }
+    }
+
+    //Buttons held during frame, None when it isn't recorded
+    pub fn buttons(&self, frame: u64) -> Option<u8> {
+        self.movie
+            .frames
+            .get(frame as usize)
+            .map(|frame| frame.buttons)
+    }
+
+    //Called when the absolute frame ended with these buttons and this hash, returns the
+    //buttons for the next one
+    pub fn end_frame(&mut self, frame: u64, buttons: u8, hash: u64) -> Option<u8> {
+        let frame = frame - self.start_frame;
+
+        match self.mode {
+            MovieMode::Recording => {
+                self.movie.frames.truncate(frame as usize);
+                self.movie.frames.push(MovieFrame { buttons, hash });
+                None
+            }
+            MovieMode::Playing { read_only } => {
+                let recorded = self.movie.frames.get(frame as usize);
+                if recorded.is_some_and(|recorded| recorded.hash != hash) && self.desync.is_none() {
+                    self.desync = Some(frame);
+                }
+
+                let next = self.buttons(frame + 1);
+                if next.is_none() {
+                    self.mode = if read_only {
+                        MovieMode::Finished
+                    } else {
+                        MovieMode::Recording
+                    };
+                }
+                next
+            }
+            MovieMode::Finished => None,
+        }
+    }
+
+    //Save states have to be from within the movie, frame is absolute
+    pub fn check_seek(&self, frame: u64) -> io::Result<()> {
+        let length = self.movie.frames.len() as u64;
+
+        if frame < self.start_frame {
+            Err(invalid("State is from before the movie started"))
+        } else if frame - self.start_frame > length {
+            Err(invalid(format!(
+                "State is at frame {} of the movie, which only has {}",
+                frame - self.start_frame,
+                length
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    //A save state at frame was loaded, returns the buttons to hold from there
+    pub fn seek(&mut self, frame: u64) -> Option<u8> {
+        let frame = frame - self.start_frame;
+
+        match self.mode {
+            MovieMode::Recording | MovieMode::Playing { read_only: false } => {
+                //Re-recording from here, the rest of the movie is replaced
+                self.movie.frames.truncate(frame as usize);
+                self.mode = MovieMode::Recording;
+                self.desync = self.desync.filter(|&desync| desync < frame);
+                None
+            }
+            MovieMode::Playing { read_only: true } | MovieMode::Finished => {
+                self.mode = match self.buttons(frame) {
+                    Some(_) => MovieMode::Playing { read_only: true },
+                    None => MovieMode::Finished,
+                };
+                self.buttons(frame)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> CartridgeHeader {
+        let mut title = [0; 16];
+        title[..4].copy_from_slice(b"TEST");
+
+        CartridgeHeader {
+            title,
+            cgb_flag: 0,
+            new_licensee: [0; 2],
+            cartridge_type: 0,
+            old_licensee: 0,
+            header_checksum: 0x42,
+            global_checksum: 0xBEEF,
+        }
+    }
+
+    fn movie(start: MovieStart, length: u8) -> Movie {
+        let mut movie = Movie::new(&header(), Model::Dmg, start);
+        movie.frames = (0..length)
+            .map(|frame| MovieFrame {
+                buttons: frame,
+                hash: frame as u64 * 1000,
+            })
+            .collect();
+        movie
+    }
+
+    fn session(movie: Movie, mode: MovieMode) -> MovieSession {
+        MovieSession {
+            movie,
+            mode,
+            start_frame: 100,
+            desync: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for start in [
+            MovieStart::PowerOn(BootMode::Builtin),
+            MovieStart::State(vec![1, 2, 3, 4, 5]),
+        ] {
+            let movie = movie(start, 20);
+            assert_eq!(Movie::from_bytes(&movie.to_bytes()).unwrap(), movie);
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = movie(MovieStart::PowerOn(BootMode::NoBootrom), 3).to_bytes();
+
+        let mut not_a_movie = bytes.clone();
+        not_a_movie[0] = b'X';
+        let mut newer = bytes.clone();
+        newer[8] = VERSION as u8 + 1;
+        let mut model = bytes.clone();
+        model[29] = 7;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+
+        for (data, message) in [
+            (&not_a_movie[..], "Not a movie"),
+            (
+                &newer[..],
+                "Movie is version 2, this build only reads version 1",
+            ),
+            (&model[..], "Invalid model 7"),
+            (&trailing[..], "Movie has trailing data"),
+            (&bytes[..bytes.len() - 1], "Save state is truncated"),
+        ] {
+            let err = Movie::from_bytes(data).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn checks_the_game() {
+        let movie = movie(MovieStart::PowerOn(BootMode::Builtin), 0);
+        assert!(movie.check(&header(), Model::Dmg).is_ok());
+        assert!(movie.check(&header(), Model::Cgb).is_err());
+
+        let mut other = header();
+        other.global_checksum = 0x1234;
+        assert!(movie.check(&other, Model::Dmg).is_err());
+
+        assert!(movie.check_boot(BootMode::Builtin).is_ok());
+        assert!(movie.check_boot(BootMode::Bootrom).is_err());
+    }
+
+    #[test]
+    fn recording() {
+        let mut session = session(
+            movie(MovieStart::PowerOn(BootMode::NoBootrom), 0),
+            MovieMode::Recording,
+        );
+
+        assert_eq!(session.end_frame(100, 0x01, 11), None);
+        assert_eq!(session.end_frame(101, 0x80, 22), None);
+        assert_eq!(
+            session.movie.frames,
+            [
+                MovieFrame {
+                    buttons: 0x01,
+                    hash: 11
+                },
+                MovieFrame {
+                    buttons: 0x80,
+                    hash: 22
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn playback_reports_the_first_desync() {
+        let mut session = session(
+            movie(MovieStart::PowerOn(BootMode::NoBootrom), 4),
+            MovieMode::Playing { read_only: true },
+        );
+
+        assert_eq!(session.end_frame(100, 0, 0), Some(1));
+        assert_eq!(session.end_frame(101, 1, 5), Some(2));
+        assert_eq!(session.end_frame(102, 2, 6), Some(3));
+        assert_eq!(session.desync, Some(1));
+
+        assert_eq!(session.end_frame(103, 3, 3000), None);
+        assert_eq!(session.mode, MovieMode::Finished);
+    }
+
+    #[test]
+    fn read_write_playback_records_after_the_end() {
+        let mut session = session(
+            movie(MovieStart::PowerOn(BootMode::NoBootrom), 2),
+            MovieMode::Playing { read_only: false },
+        );
+
+        session.end_frame(100, 0, 0);
+        assert_eq!(session.end_frame(101, 1, 1000), None);
+        assert_eq!(session.mode, MovieMode::Recording);
+    }
+
+    #[test]
+    fn seeking() {
+        let mut read_only = session(
+            movie(MovieStart::PowerOn(BootMode::NoBootrom), 10),
+            MovieMode::Finished,
+        );
+        assert!(read_only.check_seek(99).is_err());
+        assert!(read_only.check_seek(111).is_err());
+        assert!(read_only.check_seek(110).is_ok());
+
+        assert_eq!(read_only.seek(104), Some(4));
+        assert_eq!(read_only.mode, MovieMode::Playing { read_only: true });
+        assert_eq!(read_only.movie.frames.len(), 10);
+
+        let mut read_write = session(
+            movie(MovieStart::PowerOn(BootMode::NoBootrom), 10),
+            MovieMode::Playing { read_only: false },
+        );
+        assert_eq!(read_write.seek(104), None);
+        assert_eq!(read_write.mode, MovieMode::Recording);
+        assert_eq!(read_write.movie.frames.len(), 4);
+    }
+}
@@ -0,0 +1,190 @@
+use std::{fs, io};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+//Largest payload of an uncompressed deflate block
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+//zlib stream made of stored (uncompressed) deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+
+        out.push(is_final as u8);
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+//8-bit RGB PNG, pixels are 0x00RRGGBB
+pub fn encode_png(pixels: &[u32], width: usize, height: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    //Every scanline starts with its filter type, always 0 (none) here
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels.chunks(width).take(height) {
+        raw.push(0);
+        for &pixel in row {
+            raw.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+        }
+    }
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    push_chunk(&mut png, b"IHDR", &header);
+    push_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    push_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+pub fn write_png(path: &str, pixels: &[u32], width: usize, height: usize) -> io::Result<()> {
+    fs::write(path, encode_png(pixels, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Walks the chunks, checking each CRC, and returns (kind, data) pairs
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let body = &rest[4..8 + length];
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(body));
+
+            chunks.push((body[..4].try_into().unwrap(), body[4..].to_vec()));
+            rest = &rest[12 + length..];
+        }
+
+        chunks
+    }
+
+    //Inflates a zlib stream made of stored blocks only
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(zlib[..2], [0x78, 0x01]);
+
+        let mut data = Vec::new();
+        let mut position = 2;
+        loop {
+            let is_final = zlib[position] & 1 == 1;
+            let length = u16::from_le_bytes([zlib[position + 1], zlib[position + 2]]);
+            let complement = u16::from_le_bytes([zlib[position + 3], zlib[position + 4]]);
+            assert_eq!(length, !complement);
+
+            position += 5;
+            data.extend_from_slice(&zlib[position..position + length as usize]);
+            position += length as usize;
+
+            if is_final {
+                break;
+            }
+        }
+
+        let adler = u32::from_be_bytes(zlib[position..position + 4].try_into().unwrap());
+        assert_eq!(adler, adler32(&data));
+        assert_eq!(position + 4, zlib.len());
+
+        data
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn header() {
+        let png = encode_png(&[0; 160 * 144], 160, 144);
+        assert_eq!(png[..8], PNG_SIGNATURE);
+
+        let chunks = chunks(&png);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(
+            chunks[0].1,
+            [0, 0, 0, 160, 0, 0, 0, 144, 8, 2, 0, 0, 0],
+            "160x144, 8-bit RGB"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        //Big enough to need more than one stored block
+        let (width, height) = (160, 144);
+        let pixels: Vec<u32> = (0..width * height).map(|i| i as u32 * 0x010203).collect();
+
+        let png = encode_png(&pixels, width, height);
+        let raw = inflate_stored(&chunks(&png)[1].1);
+        assert!(raw.len() > MAX_STORED_BLOCK);
+
+        let rows: Vec<&[u8]> = raw.chunks(width * 3 + 1).collect();
+        assert_eq!(rows.len(), height);
+        for (y, row) in rows.iter().enumerate() {
+            assert_eq!(row[0], 0);
+            for (x, rgb) in row[1..].chunks(3).enumerate() {
+                let pixel = pixels[y * width + x];
+                assert_eq!(rgb, [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+            }
+        }
+    }
+}
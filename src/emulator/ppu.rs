@@ -1,8 +1,11 @@
+use std::io;
+
 use log::debug;
 
 use super::{
     bus::Bus,
     interrupt::{INT_LCD, INT_VBLANK},
+    savestate::{invalid, SaveState, StateReader, StateWriter},
 };
 
 pub const SCREEN_WIDTH: u8 = 160;
@@ -46,6 +49,12 @@ pub const STAT_MODE2_INT: u8 = 5;
 pub const STAT_MODE1_INT: u8 = 4;
 pub const STAT_MODE0_INT: u8 = 3;
 
+//Which layer (and so which palette) produced a pixel in the frame buffer
+pub const LAYER_BG: u8 = 0;
+pub const LAYER_WIN: u8 = 1;
+pub const LAYER_OBJ0: u8 = 2;
+pub const LAYER_OBJ1: u8 = 3;
+
 #[allow(non_camel_case_types)]
 #[derive(Debug)]
 pub enum PpuMode {
@@ -55,9 +64,31 @@ pub enum PpuMode {
     VBLANK,   //Mode 1
 }
 
+//Host side debug switches, they only change what ends up in the frame buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerToggles {
+    pub bg: bool,
+    pub window: bool,
+    pub sprites: bool,
+    //Tint every pixel by the layer it came from
+    pub tint: bool,
+}
+
+impl Default for LayerToggles {
+    fn default() -> Self {
+        Self {
+            bg: true,
+            window: true,
+            sprites: true,
+            tint: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Ppu {
     pub frame_buffer: [u8; 160 * 144],
+    pub layer_buffer: [u8; 160 * 144],
     pub current_cycle: i32,
 
     window_line: u8,
@@ -85,12 +116,15 @@ pub struct Ppu {
     pub bgp: u8,
     pub obp0: u8,
     pub obp1: u8,
+
+    pub layers: LayerToggles,
 }
 
 impl Default for Ppu {
     fn default() -> Ppu {
         Ppu {
             frame_buffer: [0; 160 * 144],
+            layer_buffer: [LAYER_BG; 160 * 144],
             mode: PpuMode::OAM_SCAN,
             current_cycle: 0,
             oam_buffer: Vec::new(),
@@ -116,6 +150,8 @@ impl Default for Ppu {
             bgp: 0,
             obp0: 0,
             obp1: 0,
+
+            layers: LayerToggles::default(),
         }
     }
 }
@@ -184,6 +220,7 @@ impl Ppu {
         if !self.get_lcdc(LCDC_PRIORITY) {
             for x in 0..160 {
                 self.frame_buffer[x + (self.ly as usize) * 160] = 0;
+                self.layer_buffer[x + (self.ly as usize) * 160] = LAYER_BG;
             }
 
             return [0u8; 160];
@@ -209,26 +246,31 @@ impl Ppu {
                 self.fetch_background_tile(x, self.ly, self.scx, self.scy)
             };
 
-            let tile_address = if is_8000 {
-                TILEDATA_START_ADDR + tile_index * 16 + line_offset * 2
+            let pixel = self.tile_pixel(is_8000, tile_index, line_offset, shift);
+            color_index[x as usize] = pixel;
+
+            //Hiding the window shows the background behind it
+            let (pixel, layer) = if is_window && !self.layers.window {
+                let (tile_index, line_offset, shift) =
+                    self.fetch_background_tile(x, self.ly, self.scx, self.scy);
+                (
+                    self.tile_pixel(is_8000, tile_index, line_offset, shift),
+                    LAYER_BG,
+                )
+            } else if is_window {
+                (pixel, LAYER_WIN)
             } else {
-                let index = tile_index as i8 as i16;
-                0x9000u16.wrapping_add_signed(index * 16) + line_offset * 2
+                (pixel, LAYER_BG)
             };
 
-            let lo = self.read_vram(tile_address);
-            let hi = self.read_vram(tile_address + 1);
-
-            //HACK: naive render
-            let lo_bit = (lo >> shift) & 1;
-            let hi_bit = (hi >> shift) & 1;
-
-            let pixel = (hi_bit << 1) | lo_bit;
-
-            let color = (self.bgp >> (pixel * 2)) & 0x03;
+            let color = if layer == LAYER_BG && !self.layers.bg {
+                0
+            } else {
+                (self.bgp >> (pixel * 2)) & 0x03
+            };
 
-            color_index[x as usize] = pixel;
             self.frame_buffer[(x as usize) + (self.ly as usize) * 160] = color;
+            self.layer_buffer[(x as usize) + (self.ly as usize) * 160] = layer;
         }
 
         if window_visible {
@@ -238,6 +280,24 @@ impl Ppu {
         color_index
     }
 
+    fn tile_pixel(&self, is_8000: bool, tile_index: u16, line_offset: u16, shift: u8) -> u8 {
+        let tile_address = if is_8000 {
+            TILEDATA_START_ADDR + tile_index * 16 + line_offset * 2
+        } else {
+            let index = tile_index as i8 as i16;
+            0x9000u16.wrapping_add_signed(index * 16) + line_offset * 2
+        };
+
+        let lo = self.read_vram(tile_address);
+        let hi = self.read_vram(tile_address + 1);
+
+        //HACK: naive render
+        let lo_bit = (lo >> shift) & 1;
+        let hi_bit = (hi >> shift) & 1;
+
+        (hi_bit << 1) | lo_bit
+    }
+
     fn fetch_window_tile(&self, x: u8, y: u8, wx: u8, wy: u8) -> (u16, u16, u8) {
         let relative_x = x + 7 - wx;
         let relative_y = y - wy;
@@ -281,7 +341,7 @@ impl Ppu {
     }
 
     fn draw_lcd_sprite(&mut self, color_index: [u8; 160]) {
-        if !self.get_lcdc(LCDC_OBJ_ENABLE) {
+        if !self.get_lcdc(LCDC_OBJ_ENABLE) || !self.layers.sprites {
             return;
         }
 
@@ -322,10 +382,10 @@ impl Ppu {
             let lo = self.read_vram(line_address);
             let hi = self.read_vram(line_address + 1);
 
-            let pallete = if (obj_flags >> 4) & 1 == 0 {
-                self.obp0
+            let (pallete, layer) = if (obj_flags >> 4) & 1 == 0 {
+                (self.obp0, LAYER_OBJ0)
             } else {
-                self.obp1
+                (self.obp1, LAYER_OBJ1)
             };
 
             //HACK: naive render!
@@ -348,6 +408,7 @@ impl Ppu {
                     let color = (pallete >> (pixel * 2)) & 0x3;
 
                     self.frame_buffer[(screen_x as usize) + (self.ly as usize) * 160] = color;
+                    self.layer_buffer[(screen_x as usize) + (self.ly as usize) * 160] = layer;
                 }
             }
         }
@@ -473,6 +534,27 @@ impl Ppu {
         self.ly
     }
 
+    //T-cycles spent in the current mode before update moves on
+    fn mode_length(&self) -> i32 {
+        match self.mode {
+            PpuMode::OAM_SCAN => 80,
+            PpuMode::DRAW => 172,
+            PpuMode::HBLANK => 204,
+            PpuMode::VBLANK => CYCLES_PER_SCANLINE,
+        }
+    }
+
+    //Position within the current scanline, 0-455
+    pub fn dot(&self) -> u16 {
+        let start = match self.mode {
+            PpuMode::OAM_SCAN | PpuMode::VBLANK => 0,
+            PpuMode::DRAW => 80,
+            PpuMode::HBLANK => 80 + 172,
+        };
+
+        (start + self.current_cycle) as u16
+    }
+
     pub fn reset_stat(&mut self, stat: u8) {
         self.stat &= !(1 << stat);
     }
@@ -490,3 +572,135 @@ impl Ppu {
         ((self.stat & mask) >> stat) & 1 == 1
     }
 }
+
+//Layer toggles are host side and stay as they are
+impl SaveState for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(match self.mode {
+            PpuMode::HBLANK => 0,
+            PpuMode::VBLANK => 1,
+            PpuMode::OAM_SCAN => 2,
+            PpuMode::DRAW => 3,
+        });
+        state.i32(self.current_cycle);
+        state.u8(self.ly);
+        state.u8(self.window_line);
+        state.bool(self.finish_frame);
+
+        //Sprites picked by the last OAM scan, drawn by the current line. Always 10 slots
+        //so the rest of the state doesn't move when the count changes
+        state.u8(self.oam_buffer.len() as u8);
+        for slot in 0..10 {
+            state.u8(self.oam_buffer.get(slot).copied().unwrap_or(0) as u8);
+        }
+
+        state.u8(self.dma_transfer);
+        state.bool(self.dma_transfer_active);
+        state.i32(self.dma_transfer_cycle);
+
+        for register in [
+            self.lcdc, self.stat, self.lyc, self.scx, self.scy, self.wx, self.wy, self.bgp,
+            self.obp0, self.obp1,
+        ] {
+            state.u8(register);
+        }
+
+        state.bytes(&self.vram);
+        state.bytes(&self.oam);
+        state.bytes(&self.frame_buffer);
+        state.bytes(&self.layer_buffer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        self.mode = match state.u8()? {
+            0 => PpuMode::HBLANK,
+            1 => PpuMode::VBLANK,
+            2 => PpuMode::OAM_SCAN,
+            3 => PpuMode::DRAW,
+            mode => return Err(invalid(format!("Invalid PPU mode {}", mode))),
+        };
+        self.current_cycle = state.i32()?;
+        if !(0..self.mode_length()).contains(&self.current_cycle) {
+            return Err(invalid(format!(
+                "PPU cycle {} is outside of {:?}",
+                self.current_cycle, self.mode
+            )));
+        }
+
+        //next_scanline only wraps after line 154
+        self.ly = state.u8()?;
+        if self.ly > 154 {
+            return Err(invalid(format!("Invalid LY {}", self.ly)));
+        }
+        self.window_line = state.u8()?;
+        self.finish_frame = state.bool()?;
+
+        let sprites = state.u8()? as usize;
+        if sprites > 10 {
+            return Err(invalid(format!("Invalid sprite count {}", sprites)));
+        }
+        let slots = state.array::<10>()?;
+        self.oam_buffer = Vec::with_capacity(sprites);
+        for &address in &slots[..sprites] {
+            if address as usize >= self.oam.len() {
+                return Err(invalid(format!("Invalid OAM entry {}", address)));
+            }
+            self.oam_buffer.push(address as usize);
+        }
+
+        self.dma_transfer = state.u8()?;
+        self.dma_transfer_active = state.bool()?;
+        self.dma_transfer_cycle = state.i32()?;
+
+        for register in [
+            &mut self.lcdc,
+            &mut self.stat,
+            &mut self.lyc,
+            &mut self.scx,
+            &mut self.scy,
+            &mut self.wx,
+            &mut self.wy,
+            &mut self.bgp,
+            &mut self.obp0,
+            &mut self.obp1,
+        ] {
+            *register = state.u8()?;
+        }
+
+        state.fill(&mut self.vram)?;
+        state.fill(&mut self.oam)?;
+        state.fill(&mut self.frame_buffer)?;
+        state.fill(&mut self.layer_buffer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(ppu: &Ppu) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        ppu.save_state(&mut state);
+        state.into_bytes()
+    }
+
+    #[test]
+    fn state_layout_ignores_the_sprite_count() {
+        let mut ppu = Ppu::default();
+        let empty = saved(&ppu);
+
+        ppu.oam_buffer = vec![0, 4, 8];
+        let three = saved(&ppu);
+        assert_eq!(empty.len(), three.len());
+        //Only the count and the slots differ
+        let differing: Vec<usize> = (0..empty.len())
+            .filter(|&index| empty[index] != three[index])
+            .collect();
+        assert_eq!(differing, [8, 10, 11]);
+
+        let mut loaded = Ppu::default();
+        loaded.load_state(&mut StateReader::new(&three)).unwrap();
+        assert_eq!(loaded.oam_buffer, [0, 4, 8]);
+    }
+}
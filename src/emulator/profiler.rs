@@ -0,0 +1,290 @@
+use std::{collections::HashMap, fmt::Write as _, fs, io};
+
+use super::{
+    callstack::{CallFrame, CallKind, MAX_DEPTH},
+    cpu::Cpu,
+    debug::Location,
+    gameboy::CYCLES_PER_FRAME,
+    symbols::Symbols,
+};
+
+const HOT_INSTRUCTIONS: usize = 32;
+
+//One routine reached through one particular chain of calls
+#[derive(Debug, Clone)]
+struct Node {
+    parent: Option<usize>,
+    routine: Location,
+    //Cycles spent in the routine itself
+    cycles: u64,
+    calls: u64,
+}
+
+//Node of a call on the CPU's shadow call stack
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    node: usize,
+    call: CallFrame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutineProfile {
+    pub routine: Location,
+    pub calls: u64,
+    //Cycles of the routine and everything it called
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+//Charges the T-cycles of every step to the running instruction and to the call tree,
+//which follows Cpu::call_stack
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    nodes: Vec<Node>,
+    children: HashMap<(usize, Location), usize>,
+    //Below the root node, which is the routine running when profiling started
+    frames: Vec<Frame>,
+    instructions: HashMap<Location, u64>,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    fn child(&mut self, parent: usize, routine: Location) -> usize {
+        if let Some(&node) = self.children.get(&(parent, routine)) {
+            return node;
+        }
+
+        self.nodes.push(Node {
+            parent: Some(parent),
+            routine,
+            cycles: 0,
+            calls: 0,
+        });
+        let node = self.nodes.len() - 1;
+        self.children.insert((parent, routine), node);
+        node
+    }
+
+    fn top(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.node)
+    }
+
+    //Follows the calls made and returned from since the last step
+    fn follow(&mut self, calls: &[CallFrame]) {
+        //A full call stack drops its oldest frames, the nodes below stay where they are
+        let dropped = match calls.first() {
+            Some(first) if calls.len() == MAX_DEPTH => self
+                .frames
+                .iter()
+                .position(|frame| frame.call == *first)
+                .unwrap_or(0),
+            _ => 0,
+        };
+        self.frames.drain(..dropped);
+
+        let same = self
+            .frames
+            .iter()
+            .zip(calls)
+            .take_while(|(frame, call)| frame.call == **call)
+            .count();
+        self.frames.truncate(same);
+
+        for &call in &calls[same..] {
+            let node = self.child(self.top(), call.target);
+            self.nodes[node].calls += 1;
+            self.frames.push(Frame { node, call });
+        }
+    }
+
+    fn charge(&mut self, node: usize, location: Location, cycles: u64) {
+        self.nodes[node].cycles += cycles;
+        *self.instructions.entry(location).or_default() += cycles;
+        self.total += cycles;
+    }
+
+    //Called after every step with the instruction location from before it
+    pub fn record(&mut self, location: Location, cycles: i32, cpu: &Cpu) {
+        let cycles = cycles as u64;
+
+        if self.nodes.is_empty() {
+            self.nodes.push(Node {
+                parent: None,
+                routine: location,
+                cycles: 0,
+                calls: 1,
+            });
+
+            //Calls made before profiling started are charged to the root
+            self.frames = cpu
+                .call_stack
+                .frames()
+                .iter()
+                .map(|&call| Frame { node: 0, call })
+                .collect();
+        }
+
+        //CALL and RET belong to the routine that ran them
+        let caller = self.top();
+        self.follow(cpu.call_stack.frames());
+
+        //Dispatch and the first instruction of the handler belong to the handler
+        let handler = cpu.dispatched_interrupt.and_then(|_| {
+            self.frames
+                .iter()
+                .rev()
+                .find(|frame| matches!(frame.call.kind, CallKind::Interrupt(_)))
+        });
+
+        match handler {
+            Some(&Frame { node, call }) => self.charge(node, call.target, cycles),
+            None => self.charge(caller, location, cycles),
+        }
+    }
+
+    //Per routine totals, a recursive routine is only counted once in its inclusive time
+    pub fn routines(&self) -> Vec<RoutineProfile> {
+        let mut subtree: Vec<u64> = self.nodes.iter().map(|node| node.cycles).collect();
+        //Children are always created after their parent
+        for index in (1..self.nodes.len()).rev() {
+            if let Some(parent) = self.nodes[index].parent {
+                subtree[parent] += subtree[index];
+            }
+        }
+
+        let mut routines: HashMap<Location, RoutineProfile> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let profile = routines.entry(node.routine).or_insert(RoutineProfile {
+                routine: node.routine,
+                calls: 0,
+                inclusive: 0,
+                exclusive: 0,
+            });
+            profile.calls += node.calls;
+            profile.exclusive += node.cycles;
+
+            let recursive = self
+                .ancestors(index)
+                .skip(1)
+                .any(|ancestor| self.nodes[ancestor].routine == node.routine);
+            if !recursive {
+                profile.inclusive += subtree[index];
+            }
+        }
+
+        let mut routines: Vec<RoutineProfile> = routines.into_values().collect();
+        routines.sort_by(|a, b| {
+            b.inclusive
+                .cmp(&a.inclusive)
+                .then(b.exclusive.cmp(&a.exclusive))
+                .then(a.routine.cmp(&b.routine))
+        });
+        routines
+    }
+
+    //The node itself first, then up to the root
+    fn ancestors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(node), |&node| self.nodes[node].parent)
+    }
+
+    fn percent(&self, cycles: u64) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            cycles as f64 * 100.0 / self.total as f64
+        }
+    }
+
+    //Flat report: routines by inclusive time, then the most expensive instructions
+    pub fn report(&self, symbols: &Symbols) -> String {
+        let mut report = String::new();
+
+        let _ = writeln!(
+            report,
+            "{} T-cycles ({:.1} frames)\n",
+            self.total,
+            self.total as f64 / CYCLES_PER_FRAME as f64
+        );
+        let _ = writeln!(
+            report,
+            "{:<12} {:>10} {:>12} {:>7} {:>12} {:>7}  Label",
+            "Routine", "Calls", "Inclusive", "%", "Exclusive", "%"
+        );
+        for profile in self.routines() {
+            let _ = writeln!(
+                report,
+                "{:<12} {:>10} {:>12} {:>7.2} {:>12} {:>7.2}  {}",
+                profile.routine.to_string(),
+                profile.calls,
+                profile.inclusive,
+                self.percent(profile.inclusive),
+                profile.exclusive,
+                self.percent(profile.exclusive),
+                symbols.name(profile.routine).unwrap_or_default()
+            );
+        }
+
+        let mut instructions: Vec<(&Location, &u64)> = self.instructions.iter().collect();
+        instructions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        let _ = writeln!(
+            report,
+            "\n{:<12} {:>12} {:>7}  Label",
+            "Instruction", "Cycles", "%"
+        );
+        for (location, &cycles) in instructions.into_iter().take(HOT_INSTRUCTIONS) {
+            let _ = writeln!(
+                report,
+                "{:<12} {:>12} {:>7.2}  {}",
+                location.to_string(),
+                cycles,
+                self.percent(cycles),
+                symbols.name(*location).unwrap_or_default()
+            );
+        }
+
+        report
+    }
+
+    //One "root;caller;callee cycles" line per call chain, the format flamegraph tools read
+    pub fn collapsed_stacks(&self, symbols: &Symbols) -> String {
+        let mut stacks = String::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.cycles == 0 {
+                continue;
+            }
+
+            let mut path: Vec<String> = self
+                .ancestors(index)
+                .map(|ancestor| symbols.describe(self.nodes[ancestor].routine))
+                .collect();
+            path.reverse();
+
+            let _ = writeln!(stacks, "{} {}", path.join(";"), node.cycles);
+        }
+
+        stacks
+    }
+
+    pub fn write_report(&self, path: &str, symbols: &Symbols) -> io::Result<()> {
+        write_profile(path, &self.report(symbols))
+    }
+
+    pub fn write_collapsed_stacks(&self, path: &str, symbols: &Symbols) -> io::Result<()> {
+        write_profile(path, &self.collapsed_stacks(symbols))
+    }
+}
+
+fn write_profile(path: &str, contents: &str) -> io::Result<()> {
+    fs::write(path, contents)
+        .map_err(|err| io::Error::new(err.kind(), format!("Can't write profile {path}: {err}")))
+}
@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+
+//Shorter zero runs are cheaper to keep inside a literal than to split it
+const MIN_ZERO_RUN: usize = 8;
+
+fn push_u32(delta: &mut Vec<u8>, value: usize) {
+    delta.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn read_u32(delta: &[u8], at: &mut usize) -> usize {
+    let value = u32::from_le_bytes(delta[*at..*at + 4].try_into().unwrap());
+    *at += 4;
+    value as usize
+}
+
+//Older state XOR newer state, as (zero run, literal run, literal bytes) triples. Most
+//bytes don't change between snapshots, so the runs of zeros make up most of it
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let byte = |index: usize| older[index] ^ newer.get(index).copied().unwrap_or(0);
+    let zero_run_at = |index: usize| {
+        (index..(index + MIN_ZERO_RUN).min(older.len())).all(|index| byte(index) == 0)
+    };
+
+    let mut delta = Vec::new();
+    push_u32(&mut delta, older.len());
+
+    let mut index = 0;
+    while index < older.len() {
+        let zeros_start = index;
+        while index < older.len() && byte(index) == 0 {
+            index += 1;
+        }
+
+        let literal_start = index;
+        while index < older.len() && !zero_run_at(index) {
+            index += 1;
+        }
+
+        push_u32(&mut delta, literal_start - zeros_start);
+        push_u32(&mut delta, index - literal_start);
+        delta.extend((literal_start..index).map(byte));
+    }
+
+    delta
+}
+
+fn apply_delta(delta: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut at = 0;
+    let length = read_u32(delta, &mut at);
+
+    let mut older: Vec<u8> = (0..length)
+        .map(|index| newer.get(index).copied().unwrap_or(0))
+        .collect();
+
+    let mut index = 0;
+    while at < delta.len() {
+        index += read_u32(delta, &mut at);
+        let literals = read_u32(delta, &mut at);
+
+        for (byte, &diff) in older[index..index + literals]
+            .iter_mut()
+            .zip(&delta[at..at + literals])
+        {
+            *byte ^= diff;
+        }
+        index += literals;
+        at += literals;
+    }
+
+    older
+}
+
+//Save states taken every few frames. Only the newest is kept whole, every older one is
+//a delta against the one after it, and the oldest are dropped to stay within the budget
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    interval: u32,
+    budget: usize,
+
+    newest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    size: usize,
+
+    frames: u32,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, budget: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            budget,
+
+            newest: None,
+            deltas: VecDeque::new(),
+            size: 0,
+
+            frames: 0,
+        }
+    }
+
+    //Frames between snapshots, and how far back one rewind step goes
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    //Bytes used by the snapshots
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    //Called after every frame, true when a snapshot is due
+    pub fn frame_done(&mut self) -> bool {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return false;
+        }
+
+        self.frames = 0;
+        true
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(newest) = self.newest.take() {
+            let delta = encode_delta(&newest, &state);
+            self.size += delta.len();
+            self.size -= newest.len();
+            self.deltas.push_back(delta);
+        }
+
+        self.size += state.len();
+        self.newest = Some(state);
+
+        while self.size > self.budget {
+            let Some(oldest) = self.deltas.pop_front() else {
+                break;
+            };
+            self.size -= oldest.len();
+        }
+    }
+
+    //Newest snapshot, the one before it becomes the newest. The oldest one is never
+    //dropped, rewinding past it keeps returning it
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.frames = 0;
+
+        let newest = self.newest.take()?;
+        let Some(delta) = self.deltas.pop_back() else {
+            self.newest = Some(newest.clone());
+            return Some(newest);
+        };
+
+        let older = apply_delta(&delta, &newest);
+        self.size -= delta.len();
+        self.size -= newest.len();
+        self.size += older.len();
+        self.newest = Some(older);
+
+        Some(newest)
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.size = 0;
+        self.frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(seed: u8, length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|index| (index as u8).wrapping_mul(31) ^ seed)
+            .collect()
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let older = state(0, 4096);
+        let mut newer = older.clone();
+        newer[3] ^= 0xFF;
+        newer[100..110].fill(0);
+        newer[4095] = 1;
+
+        let delta = encode_delta(&older, &newer);
+        assert!(delta.len() < 64, "{} byte delta", delta.len());
+        assert_eq!(apply_delta(&delta, &newer), older);
+    }
+
+    #[test]
+    fn delta_of_identical_states() {
+        let older = state(7, 1000);
+        let delta = encode_delta(&older, &older);
+        assert_eq!(delta.len(), 12);
+        assert_eq!(apply_delta(&delta, &older), older);
+    }
+
+    #[test]
+    fn delta_between_lengths() {
+        let shorter = state(1, 100);
+        let longer = state(2, 150);
+
+        assert_eq!(
+            apply_delta(&encode_delta(&shorter, &longer), &longer),
+            shorter
+        );
+        assert_eq!(
+            apply_delta(&encode_delta(&longer, &shorter), &shorter),
+            longer
+        );
+    }
+
+    #[test]
+    fn pop_walks_back() {
+        let mut rewind = RewindBuffer::new(1, usize::MAX);
+        for seed in 0..5 {
+            rewind.push(state(seed, 256));
+        }
+        assert_eq!(rewind.len(), 5);
+
+        for seed in (0..5).rev() {
+            assert_eq!(rewind.pop().unwrap(), state(seed, 256));
+        }
+        //The oldest stays
+        assert_eq!(rewind.pop().unwrap(), state(0, 256));
+        assert_eq!(rewind.len(), 1);
+    }
+
+    #[test]
+    fn budget_drops_the_oldest() {
+        let mut rewind = RewindBuffer::new(1, 1024);
+        for seed in 0..20 {
+            rewind.push(state(seed, 512));
+            assert!(rewind.size() <= 1024 || rewind.len() == 1);
+        }
+
+        assert!(rewind.len() < 20);
+        assert_eq!(rewind.pop().unwrap(), state(19, 512));
+    }
+
+    #[test]
+    fn interval() {
+        let mut rewind = RewindBuffer::new(3, usize::MAX);
+        let due: Vec<bool> = (0..6).map(|_| rewind.frame_done()).collect();
+        assert_eq!(due, [false, false, true, false, false, true]);
+    }
+}
@@ -0,0 +1,276 @@
+use std::io::{self, ErrorKind};
+
+pub const MAGIC: &[u8; 8] = b"GBRSSAVE";
+//Bump when a section changes layout, and teach migrate about the old one
+pub const VERSION: u16 = 1;
+
+pub fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+//Components write their state as a flat little endian stream
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    //Tag and length first, so a reader can tell which component a bad state broke
+    pub fn section(&mut self, tag: &[u8; 4], component: &dyn SaveState) {
+        let mut section = StateWriter::new();
+        component.save_state(&mut section);
+
+        self.bytes(tag);
+        self.u32(section.data.len() as u32);
+        self.bytes(&section.data);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < length {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Save state is truncated",
+            ));
+        }
+
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn fill(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        buffer.copy_from_slice(self.bytes(buffer.len())?);
+        Ok(())
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> io::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    //The section has to be next and has to be read to the last byte
+    pub fn section(&mut self, tag: &[u8; 4], component: &mut dyn SaveState) -> io::Result<()> {
+        let name = String::from_utf8_lossy(tag).trim().to_string();
+
+        let found = self.array::<4>()?;
+        if &found != tag {
+            return Err(invalid(format!(
+                "Expected the {} section, found {}",
+                name,
+                String::from_utf8_lossy(&found).trim()
+            )));
+        }
+
+        let length = self.u32()? as usize;
+        let mut section = StateReader::new(self.bytes(length)?);
+        component
+            .load_state(&mut section)
+            .map_err(|err| io::Error::new(err.kind(), format!("{} section: {}", name, err)))?;
+
+        if !section.is_empty() {
+            return Err(invalid(format!(
+                "{} section has {} bytes left over",
+                name,
+                section.data.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+//Upgrades the body of an older state to the current layout
+pub fn migrate(version: u16, body: &[u8]) -> io::Result<Vec<u8>> {
+    match version {
+        VERSION => Ok(body.to_vec()),
+        version if version > VERSION => Err(invalid(format!(
+            "Save state is version {}, this build only reads up to version {}",
+            version, VERSION
+        ))),
+        version => Err(invalid(format!(
+            "Save state version {} is no longer supported",
+            version
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Component {
+        a: u8,
+        b: u32,
+    }
+
+    impl SaveState for Component {
+        fn save_state(&self, state: &mut StateWriter) {
+            state.u8(self.a);
+            state.u32(self.b);
+        }
+
+        fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+            self.a = state.u8()?;
+            self.b = state.u32()?;
+            Ok(())
+        }
+    }
+
+    fn section(tag: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.bytes(tag);
+        state.u32(payload.len() as u32);
+        state.bytes(payload);
+        state.into_bytes()
+    }
+
+    #[test]
+    fn round_trip() {
+        let component = Component {
+            a: 7,
+            b: 0xDEADBEEF,
+        };
+        let mut state = StateWriter::new();
+        state.u16(0x1234);
+        state.i32(-5);
+        state.bool(true);
+        state.section(b"COMP", &component);
+        let data = state.into_bytes();
+
+        let mut state = StateReader::new(&data);
+        assert_eq!(state.u16().unwrap(), 0x1234);
+        assert_eq!(state.i32().unwrap(), -5);
+        assert!(state.bool().unwrap());
+        let mut loaded = Component::default();
+        state.section(b"COMP", &mut loaded).unwrap();
+        assert_eq!(loaded, component);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut state = StateReader::new(&[1, 2, 3]);
+        let err = state.u32().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "Save state is truncated");
+    }
+
+    #[test]
+    fn wrong_section() {
+        let data = section(b"OTHR", &[0; 5]);
+        let err = StateReader::new(&data)
+            .section(b"COMP", &mut Component::default())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Expected the COMP section, found OTHR");
+    }
+
+    #[test]
+    fn section_too_long() {
+        let data = section(b"COMP", &[0; 7]);
+        let err = StateReader::new(&data)
+            .section(b"COMP", &mut Component::default())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "COMP section has 2 bytes left over");
+    }
+
+    #[test]
+    fn section_too_short() {
+        let data = section(b"COMP", &[0; 3]);
+        let err = StateReader::new(&data)
+            .section(b"COMP", &mut Component::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "COMP section: Save state is truncated");
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(migrate(VERSION, &[1, 2, 3]).unwrap(), vec![1, 2, 3]);
+        assert!(migrate(VERSION + 1, &[]).is_err());
+        assert!(migrate(0, &[]).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    io::{self, ErrorKind},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use super::bus::Bus;
+
+//Cartridge RAM, WRAM and HRAM, where game state lives
+const SEARCH_REGIONS: [RangeInclusive<u16>; 3] =
+    [0xA000..=0xBFFF, 0xC000..=0xDFFF, 0xFF80..=0xFFFE];
+
+//16-bit values are little endian, like the CPU stores them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    U8,
+    I8,
+    U16,
+    I16,
+    Bcd8,
+    Bcd16,
+}
+
+impl ValueType {
+    pub fn size(self) -> u16 {
+        match self {
+            ValueType::U8 | ValueType::I8 | ValueType::Bcd8 => 1,
+            ValueType::U16 | ValueType::I16 | ValueType::Bcd16 => 2,
+        }
+    }
+
+    //None for bytes that aren't valid BCD
+    pub fn read(self, bus: &Bus, address: u16) -> Option<i64> {
+        let lo = bus.peek_byte(address);
+        let word = || u16::from_le_bytes([lo, bus.peek_byte(address.wrapping_add(1))]);
+
+        match self {
+            ValueType::U8 => Some(lo as i64),
+            ValueType::I8 => Some(lo as i8 as i64),
+            ValueType::U16 => Some(word() as i64),
+            ValueType::I16 => Some(word() as i16 as i64),
+            ValueType::Bcd8 => decode_bcd(lo as u16, 2),
+            ValueType::Bcd16 => decode_bcd(word(), 4),
+        }
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "u8" => Ok(ValueType::U8),
+            "i8" | "s8" => Ok(ValueType::I8),
+            "u16" => Ok(ValueType::U16),
+            "i16" | "s16" => Ok(ValueType::I16),
+            "bcd" | "bcd8" => Ok(ValueType::Bcd8),
+            "bcd16" => Ok(ValueType::Bcd16),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown value type {value}, expected u8, i8, u16, i16, bcd8 or bcd16"),
+            )),
+        }
+    }
+}
+
+fn decode_bcd(value: u16, digits: u32) -> Option<i64> {
+    (0..digits).rev().try_fold(0, |decoded, digit| {
+        let nibble = (value >> (digit * 4)) & 0xF;
+        (nibble < 10).then_some(decoded * 10 + nibble as i64)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+}
+
+impl Comparison {
+    fn matches(self, value: i64, operand: i64) -> bool {
+        match self {
+            Comparison::Equal => value == operand,
+            Comparison::NotEqual => value != operand,
+            Comparison::Greater => value > operand,
+            Comparison::Less => value < operand,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Self> {
+        match value {
+            "=" | "==" | "eq" => Ok(Comparison::Equal),
+            "!=" | "ne" => Ok(Comparison::NotEqual),
+            ">" | "gt" => Ok(Comparison::Greater),
+            "<" | "lt" => Ok(Comparison::Less),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown comparison {value}, expected =, !=, > or <"),
+            )),
+        }
+    }
+}
+
+//What candidates are compared against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Previous,
+    Value(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub address: u16,
+    //Value at the last snapshot or filter
+    pub previous: i64,
+}
+
+//Narrows RAM down to the addresses holding a value, one filter at a time
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    pub value_type: ValueType,
+    candidates: Vec<Candidate>,
+}
+
+impl RamSearch {
+    //Snapshot of every address whose value fits in its region
+    pub fn new(bus: &Bus, value_type: ValueType) -> Self {
+        let size = value_type.size();
+        let candidates = SEARCH_REGIONS
+            .iter()
+            .flat_map(|region| *region.start()..=region.end() + 1 - size)
+            .filter_map(|address| {
+                value_type
+                    .read(bus, address)
+                    .map(|previous| Candidate { address, previous })
+            })
+            .collect();
+
+        Self {
+            value_type,
+            candidates,
+        }
+    }
+
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    pub fn current(&self, bus: &Bus, address: u16) -> Option<i64> {
+        self.value_type.read(bus, address)
+    }
+
+    //Keeps the candidates whose current value passes, returns how many are left
+    pub fn filter(&mut self, bus: &Bus, comparison: Comparison, operand: Operand) -> usize {
+        let value_type = self.value_type;
+
+        self.candidates.retain_mut(|candidate| {
+            let Some(value) = value_type.read(bus, candidate.address) else {
+                return false;
+            };
+            let operand = match operand {
+                Operand::Previous => candidate.previous,
+                Operand::Value(operand) => operand,
+            };
+
+            candidate.previous = value;
+            comparison.matches(value, operand)
+        });
+
+        self.candidates.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::emulator::mbcs::rom::Rom;
+
+    fn bus() -> Bus {
+        Bus::new(
+            Box::new(Rom::new(vec![0; 0x8000])),
+            Rc::new(RefCell::new(Default::default())),
+            Rc::new(RefCell::new(Default::default())),
+            Rc::new(RefCell::new(Default::default())),
+            Rc::new(RefCell::new(Default::default())),
+        )
+    }
+
+    fn addresses(search: &RamSearch) -> Vec<u16> {
+        search
+            .candidates()
+            .iter()
+            .map(|candidate| candidate.address)
+            .collect()
+    }
+
+    #[test]
+    fn narrows_down_with_previous_values() {
+        let mut bus = bus();
+        let mut search = RamSearch::new(&bus, ValueType::U8);
+        //Cartridge RAM, WRAM and HRAM
+        assert_eq!(search.candidates().len(), 0x2000 + 0x2000 + 0x7F);
+
+        bus.write_byte(0xC010, 5);
+        bus.write_byte(0xFF90, 5);
+        assert_eq!(
+            search.filter(&bus, Comparison::Greater, Operand::Previous),
+            2
+        );
+        assert_eq!(addresses(&search), [0xC010, 0xFF90]);
+
+        bus.write_byte(0xC010, 4);
+        assert_eq!(search.filter(&bus, Comparison::Less, Operand::Previous), 1);
+        assert_eq!(addresses(&search), [0xC010]);
+        assert_eq!(search.candidates()[0].previous, 4);
+
+        assert_eq!(search.filter(&bus, Comparison::Equal, Operand::Previous), 1);
+        assert_eq!(
+            search.filter(&bus, Comparison::NotEqual, Operand::Value(4)),
+            0
+        );
+    }
+
+    #[test]
+    fn compares_against_a_value() {
+        let mut bus = bus();
+        bus.write_byte(0xC000, 0x80);
+        bus.write_byte(0xD000, 0x7F);
+
+        let mut search = RamSearch::new(&bus, ValueType::I8);
+        search.filter(&bus, Comparison::Less, Operand::Value(-100));
+        assert_eq!(addresses(&search), [0xC000]);
+        assert_eq!(search.current(&bus, 0xC000), Some(-128));
+
+        let mut search = RamSearch::new(&bus, ValueType::U8);
+        search.filter(&bus, Comparison::Equal, Operand::Value(0x7F));
+        assert_eq!(addresses(&search), [0xD000]);
+    }
+
+    #[test]
+    fn words_are_little_endian_and_stay_in_their_region() {
+        let mut bus = bus();
+        bus.write_byte(0xC100, 0x34);
+        bus.write_byte(0xC101, 0x12);
+
+        let mut search = RamSearch::new(&bus, ValueType::U16);
+        assert!(!addresses(&search).contains(&0xDFFF));
+        assert!(!addresses(&search).contains(&0xFFFE));
+
+        search.filter(&bus, Comparison::Equal, Operand::Value(0x1234));
+        assert_eq!(addresses(&search), [0xC100]);
+    }
+
+    #[test]
+    fn invalid_bcd_is_dropped() {
+        let mut bus = bus();
+        bus.write_byte(0xC000, 0x99);
+        bus.write_byte(0xC001, 0x9A);
+
+        let mut search = RamSearch::new(&bus, ValueType::Bcd8);
+        assert!(!addresses(&search).contains(&0xC001));
+
+        search.filter(&bus, Comparison::Equal, Operand::Value(99));
+        assert_eq!(addresses(&search), [0xC000]);
+
+        //Becoming invalid BCD drops a candidate too
+        bus.write_byte(0xC000, 0xA0);
+        assert_eq!(
+            search.filter(&bus, Comparison::NotEqual, Operand::Value(0)),
+            0
+        );
+    }
+
+    #[test]
+    fn parses_types_and_comparisons() {
+        assert_eq!("S16".parse::<ValueType>().unwrap(), ValueType::I16);
+        assert_eq!("bcd".parse::<ValueType>().unwrap(), ValueType::Bcd8);
+        assert!("u32".parse::<ValueType>().is_err());
+        assert_eq!("!=".parse::<Comparison>().unwrap(), Comparison::NotEqual);
+        assert!("<=".parse::<Comparison>().is_err());
+    }
+}
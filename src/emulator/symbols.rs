@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use log::info;
+
+use super::debug::Location;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub start: Location,
+    pub end: u16,
+}
+
+//Labels and sections from RGBDS (or WLA) .sym and .map files
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    labels: BTreeMap<Location, String>,
+    by_name: HashMap<String, Location>,
+    sections: Vec<Section>,
+}
+
+//Symbol files give a bank for every address, only keep it where the bus switches banks
+fn symbol_location(bank: usize, address: u16) -> Location {
+    let bank = match address {
+        0x0000..=0x7FFF | 0xA000..=0xBFFF => Some(bank),
+        _ => None,
+    };
+
+    Location { bank, address }
+}
+
+//Labels only reach as far as their memory area, WRAM labels don't name HRAM
+fn same_area(a: u16, b: u16) -> bool {
+    let area = |address: u16| match address {
+        0x0000..=0x3FFF => 0,
+        0x4000..=0x7FFF => 1,
+        0x8000..=0x9FFF => 2,
+        0xA000..=0xBFFF => 3,
+        0xC000..=0xFDFF => 4,
+        0xFE00..=0xFF7F => 5,
+        _ => 6,
+    };
+
+    area(a) == area(b)
+}
+
+//Local labels (Func.loop, func@loop) lose to the routine name at the same address
+fn is_local(name: &str) -> bool {
+    name.contains('.') || name.contains('@')
+}
+
+fn parse_hex<T: TryFrom<u32>>(digits: &str) -> Option<T> {
+    let digits = digits.trim().trim_start_matches('$');
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+}
+
+impl Symbols {
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.sections.is_empty()
+    }
+
+    pub fn add_label(&mut self, location: Location, name: &str) {
+        self.by_name.entry(name.to_string()).or_insert(location);
+
+        match self.labels.get(&location) {
+            Some(existing) if !is_local(existing) || is_local(name) => (),
+            _ => {
+                self.labels.insert(location, name.to_string());
+            }
+        }
+    }
+
+    //"BB:AAAA Name" lines, anything else (comments, WLA section tables, constants) is skipped
+    pub fn parse_sym(&mut self, text: &str) -> usize {
+        let mut count = 0;
+
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("");
+            let [location, name] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                continue;
+            };
+            let Some((bank, address)) = location.split_once(':') else {
+                continue;
+            };
+
+            if let (Some(bank), Some(address)) = (parse_hex(bank), parse_hex(address)) {
+                self.add_label(symbol_location(bank, address), name);
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    //rgblink -m output: "ROMX bank #1:" headers, then SECTION lines and "$4000 = Label" lines
+    pub fn parse_map(&mut self, text: &str) -> usize {
+        let mut bank = 0;
+        let mut count = 0;
+
+        for line in text.lines() {
+            let line = line.trim();
+
+            //Bank numbers are decimal here
+            if let Some((_, number)) = line.to_ascii_lowercase().split_once("bank #") {
+                bank = number.trim_end_matches(':').trim().parse().unwrap_or(0);
+            } else if let Some(section) = line.strip_prefix("SECTION:") {
+                if let Some(section) = Self::parse_section(section, bank) {
+                    self.sections.push(section);
+                    count += 1;
+                }
+            } else if let Some((address, name)) = line.split_once(" = ") {
+                if let Some(address) = parse_hex(address) {
+                    self.add_label(symbol_location(bank, address), name.trim());
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    //$0150-$01FF ($00B0 bytes) ["Main"], or $0150 (0 bytes) ["Empty"]
+    fn parse_section(line: &str, bank: usize) -> Option<Section> {
+        let (range, rest) = line.trim().split_once(char::is_whitespace)?;
+        let name = rest.split_once("[\"")?.1.rsplit_once("\"]")?.0;
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_hex(start)?, parse_hex(end)?),
+            None => (parse_hex(range)?, parse_hex(range)?),
+        };
+
+        Some(Section {
+            name: name.to_string(),
+            start: symbol_location(bank, start),
+            end,
+        })
+    }
+
+    pub fn load_sym(&mut self, path: &Path) -> io::Result<()> {
+        let text = read(path)?;
+        let count = self.parse_sym(&text);
+        info!(target: "Symbols", "Loaded {} labels from {}", count, path.display());
+        Ok(())
+    }
+
+    pub fn load_map(&mut self, path: &Path) -> io::Result<()> {
+        let text = read(path)?;
+        let count = self.parse_map(&text);
+        info!(target: "Symbols", "Loaded {} sections and labels from {}", count, path.display());
+        Ok(())
+    }
+
+    //Label names are case sensitive, like in RGBDS
+    pub fn find(&self, name: &str) -> Option<Location> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn label(&self, location: Location) -> Option<&str> {
+        self.labels.get(&location).map(String::as_str)
+    }
+
+    //Closest label at or before location in the same bank, with the offset from it
+    pub fn nearest(&self, location: Location) -> Option<(&str, u16)> {
+        let (label, name) = self.labels.range(..=location).next_back()?;
+
+        if label.bank != location.bank || !same_area(label.address, location.address) {
+            return None;
+        }
+
+        Some((name, location.address - label.address))
+    }
+
+    pub fn section(&self, location: Location) -> Option<&Section> {
+        self.sections.iter().find(|section| {
+            section.start.bank == location.bank
+                && (section.start.address..=section.end).contains(&location.address)
+        })
+    }
+
+    //Label (or section) and offset naming location, e.g. PlayerUpdate+$1A
+    pub fn name(&self, location: Location) -> Option<String> {
+        self.nearest(location)
+            .or_else(|| {
+                self.section(location).map(|section| {
+                    (
+                        section.name.as_str(),
+                        location.address - section.start.address,
+                    )
+                })
+            })
+            .map(|(name, offset)| match offset {
+                0 => name.to_string(),
+                offset => format!("{}+${:X}", name, offset),
+            })
+    }
+
+    //Bank qualified name, e.g. ROM1:PlayerUpdate+$1A, or ROM1:401A without a label
+    pub fn describe(&self, location: Location) -> String {
+        match self.name(location) {
+            Some(name) => format!("{}:{}", location.region(), name),
+            None => location.to_string(),
+        }
+    }
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't read symbols {}: {}", path.display(), err),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(bank: usize, address: u16) -> Location {
+        Location {
+            bank: Some(bank),
+            address,
+        }
+    }
+
+    #[test]
+    fn sym_file() {
+        let mut symbols = Symbols::default();
+        let count = symbols.parse_sym(
+            "; File generated by rgblink\n\
+             00:0150 Main\n\
+             00:0150 Main.loop\n\
+             01:4000 PlayerUpdate ; comment\n\
+             01:401A PlayerUpdate.jump\n\
+             00:C000 wPlayerX\n\
+             [labels]\n\
+             00:ZZZZ Broken\n",
+        );
+
+        assert_eq!(count, 5);
+        assert_eq!(symbols.label(rom(0, 0x0150)), Some("Main"));
+        assert_eq!(symbols.find("PlayerUpdate"), Some(rom(1, 0x4000)));
+        assert_eq!(symbols.find("Broken"), None);
+        assert_eq!(
+            symbols.find("wPlayerX"),
+            Some(Location {
+                bank: None,
+                address: 0xC000
+            })
+        );
+    }
+
+    #[test]
+    fn nearest_label() {
+        let mut symbols = Symbols::default();
+        symbols.parse_sym("01:4000 PlayerUpdate\n02:4000 Other\n00:C000 wPlayerX\n");
+
+        assert_eq!(symbols.describe(rom(1, 0x4010)), "ROM1:PlayerUpdate+$10");
+        assert_eq!(symbols.name(rom(3, 0x4010)), None);
+        //WRAM labels don't reach into HRAM
+        let hram = Location {
+            bank: None,
+            address: 0xFF80,
+        };
+        assert_eq!(symbols.name(hram), None);
+    }
+
+    #[test]
+    fn map_file() {
+        let mut symbols = Symbols::default();
+        let count = symbols.parse_map(
+            "ROM0 bank #0:\n\
+             \tSECTION: $0150-$01FF ($00B0 bytes) [\"Main\"]\n\
+             \t         $0150 = Main\n\
+             \tEMPTY: $0200-$3FFF ($3E00 bytes)\n\
+             ROMX bank #2:\n\
+             \tSECTION: $4000 (0 bytes) [\"Empty\"]\n\
+             \tSECTION: $4100-$41FF ($0100 bytes) [\"Graphics\"]\n",
+        );
+
+        assert_eq!(count, 4);
+        assert_eq!(symbols.label(rom(0, 0x0150)), Some("Main"));
+        assert_eq!(symbols.section(rom(2, 0x4180)).unwrap().name, "Graphics");
+        assert_eq!(symbols.name(rom(2, 0x4180)).unwrap(), "Graphics+$80");
+        assert!(symbols.section(rom(1, 0x4180)).is_none());
+    }
+}
@@ -1,4 +1,10 @@
-use super::{bus::Bus, interrupt::INT_TIMER};
+use std::io;
+
+use super::{
+    bus::Bus,
+    interrupt::INT_TIMER,
+    savestate::{SaveState, StateReader, StateWriter},
+};
 
 pub const DIV: u16 = 0xFF04;
 pub const TIMA: u16 = 0xFF05;
@@ -18,12 +24,17 @@ pub struct Timer {
 impl Timer {
     pub fn update(&mut self, cycle: i32, bus: &mut Bus) {
         for _ in 0..cycle {
-            self.div = self.div.wrapping_add(1u16);
-            self.increment_tima(bus);
+            self.step(bus);
         }
     }
 
-    pub fn increment_tima(&mut self, bus: &mut Bus) {
+    //One T-cycle, true when TIMA overflowed
+    pub fn step(&mut self, bus: &mut Bus) -> bool {
+        self.div = self.div.wrapping_add(1u16);
+        self.increment_tima(bus)
+    }
+
+    pub fn increment_tima(&mut self, bus: &mut Bus) -> bool {
         let tac_enable = (self.tac >> 2) & 1 == 1;
         let bit = match self.tac & 3 {
             0 => 9,
@@ -36,16 +47,19 @@ impl Timer {
         let div_bit = (self.div >> bit) & 1 == 1;
         let tick = tac_enable && div_bit;
 
+        let mut overflow = false;
         if self.last_tick && !tick {
-            let (res, overflow) = self.tima.overflowing_add(1);
+            let (res, carry) = self.tima.overflowing_add(1);
             self.tima = res;
 
-            if overflow {
+            if carry {
                 self.tima = self.tma;
                 bus.request_interrupt(INT_TIMER);
+                overflow = true;
             }
         }
         self.last_tick = tick;
+        overflow
     }
 
     pub fn div_reset(&mut self) {
@@ -56,3 +70,22 @@ impl Timer {
         ((self.div >> 8) & 0xFF) as u8
     }
 }
+
+impl SaveState for Timer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.last_tick);
+        state.u16(self.div);
+        state.u8(self.tima);
+        state.u8(self.tma);
+        state.u8(self.tac);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        self.last_tick = state.bool()?;
+        self.div = state.u16()?;
+        self.tima = state.u8()?;
+        self.tma = state.u8()?;
+        self.tac = state.u8()?;
+        Ok(())
+    }
+}
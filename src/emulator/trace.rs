@@ -0,0 +1,249 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::RangeInclusive,
+};
+
+use log::{error, info};
+
+use super::{bus::Bus, cpu::Cpu, debug::Location, symbols::Symbols};
+
+//Which instructions end up in the trace, everything by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub range: Option<RangeInclusive<u16>>,
+    //Bank mapped at PC, 0 for 0x0000-0x3FFF
+    pub bank: Option<usize>,
+    //Stop after this many lines
+    pub limit: Option<u64>,
+}
+
+//Writes the CPU state before every instruction in the gameboy-doctor format:
+//A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+#[derive(Debug)]
+pub struct Tracer {
+    path: String,
+    writer: BufWriter<File>,
+    filter: TraceFilter,
+    lines: u64,
+    //Appends " ; ROM1:Label+$3" to every line, which gameboy-doctor won't accept
+    symbols: Option<Symbols>,
+}
+
+impl Tracer {
+    pub fn create(path: &str, filter: TraceFilter) -> io::Result<Self> {
+        let file = File::create(path).map_err(|err| {
+            io::Error::new(err.kind(), format!("Can't create trace {path}: {err}"))
+        })?;
+
+        Ok(Self {
+            path: path.to_string(),
+            writer: BufWriter::new(file),
+            filter,
+            lines: 0,
+            symbols: None,
+        })
+    }
+
+    pub fn with_labels(mut self, symbols: Symbols) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.filter.limit.is_some_and(|limit| self.lines >= limit)
+    }
+
+    fn wants(&self, cpu: &Cpu, bus: &Bus) -> bool {
+        if self
+            .filter
+            .range
+            .as_ref()
+            .is_some_and(|range| !range.contains(&cpu.pc))
+        {
+            return false;
+        }
+
+        match self.filter.bank {
+            Some(bank) => bus.bank_at(cpu.pc) == Some(bank),
+            None => true,
+        }
+    }
+
+    //Returns false once the tracer is done or failed and should be dropped
+    pub fn trace(&mut self, cpu: &Cpu, bus: &Bus) -> bool {
+        if !self.wants(cpu, bus) {
+            return true;
+        }
+
+        if let Err(err) = self.write_line(cpu, bus) {
+            error!(target: "Trace", "Failed to write trace: {}", err);
+            return false;
+        }
+
+        self.lines += 1;
+        if self.is_done() {
+            info!(target: "Trace", "Traced {} instructions, stopping", self.lines);
+            if let Err(err) = self.writer.flush() {
+                error!(target: "Trace", "Failed to write trace: {}", err);
+            }
+            return false;
+        }
+
+        true
+    }
+
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush().map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Can't write trace {}: {}", self.path, err),
+            )
+        })
+    }
+
+    fn write_line(&mut self, cpu: &Cpu, bus: &Bus) -> io::Result<()> {
+        let pc = cpu.pc;
+
+        write!(
+            self.writer,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            cpu.a,
+            cpu.f,
+            cpu.b,
+            cpu.c,
+            cpu.d,
+            cpu.e,
+            cpu.h,
+            cpu.l,
+            cpu.sp,
+            pc,
+            bus.peek_byte(pc),
+            bus.peek_byte(pc.wrapping_add(1)),
+            bus.peek_byte(pc.wrapping_add(2)),
+            bus.peek_byte(pc.wrapping_add(3)),
+        )?;
+
+        match &self.symbols {
+            Some(symbols) => writeln!(
+                self.writer,
+                " ; {}",
+                symbols.describe(Location::at(bus, pc))
+            ),
+            None => writeln!(self.writer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, rc::Rc};
+
+    use super::*;
+    use crate::emulator::mbcs::rom::Rom;
+
+    fn bus() -> Bus {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom[0x150] = 0xAF;
+
+        Bus::new(
+            Box::new(Rom::new(rom)),
+            Rc::new(RefCell::new(Default::default())),
+            Rc::new(RefCell::new(Default::default())),
+            Rc::new(RefCell::new(Default::default())),
+            Rc::new(RefCell::new(Default::default())),
+        )
+    }
+
+    //Post boot rom DMG registers
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_af(0x01B0);
+        cpu.set_bc(0x0013);
+        cpu.set_de(0x00D8);
+        cpu.set_hl(0x014D);
+        cpu.sp = 0xFFFE;
+        cpu.pc = 0x100;
+        cpu
+    }
+
+    //Traces the CPU at each PC and returns the file
+    fn trace(name: &str, tracer: impl FnOnce(&str) -> Tracer, pcs: &[u16]) -> String {
+        let path = std::env::temp_dir().join(format!("gameboy-{}-{name}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let (bus, mut cpu) = (bus(), cpu());
+        let mut tracer = tracer(path);
+        for &pc in pcs {
+            cpu.pc = pc;
+            if !tracer.trace(&cpu, &bus) {
+                break;
+            }
+        }
+        tracer.finish().unwrap();
+
+        let text = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        text
+    }
+
+    #[test]
+    fn gameboy_doctor_format() {
+        let text = trace(
+            "doctor",
+            |path| Tracer::create(path, TraceFilter::default()).unwrap(),
+            &[0x100, 0x150],
+        );
+
+        assert_eq!(
+            text,
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01\n\
+             A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:AF,00,00,00\n"
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let mut symbols = Symbols::default();
+        symbols.parse_sym("00:0150 Main\n");
+
+        let text = trace(
+            "labels",
+            |path| {
+                Tracer::create(path, TraceFilter::default())
+                    .unwrap()
+                    .with_labels(symbols)
+            },
+            &[0x150, 0x151],
+        );
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].ends_with("PC:0150 PCMEM:AF,00,00,00 ; ROM0:Main"));
+        assert!(lines[1].ends_with("PC:0151 PCMEM:00,00,00,00 ; ROM0:Main+$1"));
+    }
+
+    #[test]
+    fn filters() {
+        let filter = TraceFilter {
+            range: Some(0x0150..=0x01FF),
+            bank: Some(0),
+            limit: Some(2),
+        };
+        let text = trace(
+            "filters",
+            |path| Tracer::create(path, filter).unwrap(),
+            &[0x100, 0x150, 0x4000, 0x151, 0x152, 0x153],
+        );
+
+        let pcs: Vec<&str> = text
+            .lines()
+            .map(|line| line.split(' ').nth(9).unwrap())
+            .collect();
+        assert_eq!(pcs, ["PC:0150", "PC:0151"]);
+    }
+}
@@ -0,0 +1,170 @@
+use super::{
+    colorization::ColorPalette,
+    ppu::{
+        Ppu, LAYER_BG, LAYER_OBJ0, LAYER_OBJ1, LCDC_BG_WIN_TILE, LCDC_OBJ_SIZE, OAM_START_ADDR,
+        TILEDATA_START_ADDR,
+    },
+};
+
+//Debug views of VRAM and OAM, only reading from the PPU so emulation isn't disturbed
+
+pub const TILE_COUNT: usize = 384;
+pub const TILESET_COLUMNS: usize = 16;
+pub const TILESET_WIDTH: usize = TILESET_COLUMNS * 8;
+pub const TILESET_HEIGHT: usize = TILE_COUNT / TILESET_COLUMNS * 8;
+
+pub const TILEMAP_SIZE: usize = 256;
+pub const BG_MAP_0: u16 = 0x9800;
+pub const BG_MAP_1: u16 = 0x9C00;
+
+pub const OAM_ENTRIES: usize = 40;
+
+//Color index (0-3) of one pixel of the tile stored at address
+fn tile_pixel(ppu: &Ppu, address: u16, x: usize, y: usize) -> u8 {
+    let lo = ppu.read_vram(address + y as u16 * 2);
+    let hi = ppu.read_vram(address + y as u16 * 2 + 1);
+    let shift = 7 - x;
+
+    (((hi >> shift) & 1) << 1) | ((lo >> shift) & 1)
+}
+
+fn shade(palette_register: u8, index: u8) -> u8 {
+    (palette_register >> (index * 2)) & 0x03
+}
+
+//Where a tile map entry points to, depending on the LCDC addressing mode
+pub fn bg_tile_address(ppu: &Ppu, index: u8) -> u16 {
+    if ppu.get_lcdc(LCDC_BG_WIN_TILE) {
+        TILEDATA_START_ADDR + index as u16 * 16
+    } else {
+        0x9000u16.wrapping_add_signed(index as i8 as i16 * 16)
+    }
+}
+
+//All tiles of 0x8000-0x97FF, 16 per row, shaded with BGP
+pub fn render_tiles(ppu: &Ppu, palette: &ColorPalette) -> Vec<u32> {
+    let mut pixels = vec![0; TILESET_WIDTH * TILESET_HEIGHT];
+
+    for tile in 0..TILE_COUNT {
+        let address = TILEDATA_START_ADDR + tile as u16 * 16;
+        let left = (tile % TILESET_COLUMNS) * 8;
+        let top = (tile / TILESET_COLUMNS) * 8;
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let index = tile_pixel(ppu, address, x, y);
+                pixels[(top + y) * TILESET_WIDTH + left + x] =
+                    palette.color(LAYER_BG, shade(ppu.bgp, index));
+            }
+        }
+    }
+
+    pixels
+}
+
+//The full 32x32 tile map at map (0x9800 or 0x9C00) as 256x256 pixels
+pub fn render_tilemap(ppu: &Ppu, palette: &ColorPalette, map: u16) -> Vec<u32> {
+    let mut pixels = vec![0; TILEMAP_SIZE * TILEMAP_SIZE];
+
+    for entry in 0..32 * 32 {
+        let address = bg_tile_address(ppu, ppu.read_vram(map + entry as u16));
+        let left = (entry % 32) * 8;
+        let top = (entry / 32) * 8;
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let index = tile_pixel(ppu, address, x, y);
+                pixels[(top + y) * TILEMAP_SIZE + left + x] =
+                    palette.color(LAYER_BG, shade(ppu.bgp, index));
+            }
+        }
+    }
+
+    pixels
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    pub index: usize,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+}
+
+impl Sprite {
+    pub fn behind_bg(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    pub fn flip_y(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    pub fn flip_x(&self) -> bool {
+        self.flags & 0x20 != 0
+    }
+
+    pub fn uses_obp1(&self) -> bool {
+        self.flags & 0x10 != 0
+    }
+
+    //Fully off screen, the usual way of hiding a sprite
+    pub fn is_hidden(&self, height: u8) -> bool {
+        self.x == 0 || self.x >= 168 || self.y as u16 + height as u16 <= 16 || self.y >= 160
+    }
+}
+
+pub fn sprite_height(ppu: &Ppu) -> u8 {
+    if ppu.get_lcdc(LCDC_OBJ_SIZE) {
+        16
+    } else {
+        8
+    }
+}
+
+pub fn sprites(ppu: &Ppu) -> Vec<Sprite> {
+    (0..OAM_ENTRIES)
+        .map(|index| {
+            let address = OAM_START_ADDR + index as u16 * 4;
+            Sprite {
+                index,
+                y: ppu.read_oam(address),
+                x: ppu.read_oam(address + 1),
+                tile: ppu.read_oam(address + 2),
+                flags: ppu.read_oam(address + 3),
+            }
+        })
+        .collect()
+}
+
+//8 pixels wide and sprite_height tall, flips and palette applied, None where transparent
+pub fn render_sprite(ppu: &Ppu, palette: &ColorPalette, sprite: &Sprite) -> Vec<Option<u32>> {
+    let height = sprite_height(ppu) as usize;
+    let tile = if height == 16 {
+        sprite.tile & 0xFE
+    } else {
+        sprite.tile
+    };
+    let (register, layer) = if sprite.uses_obp1() {
+        (ppu.obp1, LAYER_OBJ1)
+    } else {
+        (ppu.obp0, LAYER_OBJ0)
+    };
+
+    let mut pixels = vec![None; 8 * height];
+    for y in 0..height {
+        let row = if sprite.flip_y() { height - 1 - y } else { y };
+        let address = TILEDATA_START_ADDR + tile as u16 * 16 + (row / 8) as u16 * 16;
+
+        for x in 0..8 {
+            let column = if sprite.flip_x() { 7 - x } else { x };
+            let index = tile_pixel(ppu, address, column, row % 8);
+            if index != 0 {
+                pixels[y * 8 + x] = Some(palette.color(layer, shade(register, index)));
+            }
+        }
+    }
+
+    pixels
+}
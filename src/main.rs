@@ -1,8 +1,32 @@
-use std::time::{Duration, Instant};
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use gameboy::{devices::screen::Screen, emulator::gameboy::Gameboy};
-use log::debug;
-use log::info;
+use gameboy::{
+    devices::{
+        config::{Config, RewindConfig, DEFAULT_CONFIG_PATH},
+        debugger::{DebugAction, Debugger},
+        headless::{self, HeadlessOptions},
+        image::screenshot_path,
+        input::{Hotkey, Input},
+        osd::Osd,
+        pacing::FramePacer,
+        palette::DisplayPalettes,
+        screen::Screen,
+        speed::{FrameBudget, SpeedControl, FAST_FORWARD_BUDGET},
+        viewer::{View, Viewers},
+    },
+    emulator::{
+        cheats::load_cheats,
+        gameboy::{Gameboy, Model, CLOCK_SPEED, CYCLES_PER_FRAME},
+        movie::{Movie, MovieMode},
+        symbols::Symbols,
+        trace::TraceFilter,
+    },
+};
+use log::{error, info};
 use num_format::{Locale, ToFormattedString};
 // use simplelog::CombinedLogger;
 // use simplelog::Config;
@@ -11,6 +35,8 @@ use num_format::{Locale, ToFormattedString};
 
 use clap::Parser;
 
+const BOOT_PALETTE_FRAMES: u64 = 120;
+
 ///A Gameboy Emulator
 #[derive(Parser, Debug)]
 struct Args {
@@ -18,77 +44,679 @@ struct Args {
     #[arg(short, long)]
     rom: String,
 
-    /// Bootrom
+    /// Boot rom to run before the cartridge, 256 bytes (DMG/MGB/SGB) or 2304 bytes (CGB)
     #[arg(short, long, default_value_t = String::new())]
     bootrom: String,
 
+    /// Play a built-in logo animation and header check instead of a boot rom
+    #[arg(long, default_value_t = false, conflicts_with = "bootrom")]
+    builtin_boot: bool,
+
+    /// Hardware model to emulate, monochrome games are colorized on cgb
+    #[arg(short, long, value_enum, default_value_t = Model::Dmg)]
+    model: Model,
+
+    /// Display palette for monochrome output (grayscale, dmg, pocket, light, high-contrast, inverted or one from the config)
+    #[arg(short, long)]
+    palette: Option<String>,
+
+    /// Key binding config, overridden by <rom>.json next to the rom
+    #[arg(short, long, default_value_t = String::from(DEFAULT_CONFIG_PATH))]
+    config: String,
+
+    /// Cheat file with one Game Genie or GameShark code per line [default: <rom>.cht if it exists]
+    #[arg(long)]
+    cheats: Option<String>,
+
+    /// Start paused, step with the frame advance hotkey
+    #[arg(long, default_value_t = false)]
+    start_paused: bool,
+
+    /// Emulation speed multiplier, e.g. 0.25 or 0.5 for slow motion
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Speed multiplier of the turbo toggle, overrides the config
+    #[arg(long)]
+    turbo_speed: Option<f64>,
+
     /// Enable logging
     #[arg(short, long, default_value_t = false)]
     logging: bool,
+
+    /// Open VRAM viewer windows, the screenshot hotkey in a viewer saves it as PNG
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "headless")]
+    view: Vec<View>,
+
+    /// Open the terminal debugger next to the game window
+    #[arg(long, default_value_t = false, conflicts_with = "headless")]
+    debug: bool,
+
+    /// Run without a window
+    #[arg(long, default_value_t = false)]
+    headless: bool,
+
+    /// Stop after this many frames (headless)
+    #[arg(long, requires = "headless")]
+    frames: Option<u64>,
+
+    /// Stop when PC reaches this hex address (headless)
+    #[arg(long, value_parser = parse_hex, requires = "headless")]
+    until_pc: Option<u16>,
+
+    /// Stop on the LD B,B software breakpoint (headless)
+    #[arg(long, default_value_t = false, requires = "headless")]
+    until_breakpoint: bool,
+
+    /// Write the final frame to this PNG (or .ppm) file (headless)
+    #[arg(long, requires = "headless")]
+    output: Option<String>,
+
+    /// Write a hash of every frame to this file (headless)
+    #[arg(long, requires = "headless")]
+    hash_log: Option<String>,
+
+    /// Log every instruction to this file in the gameboy-doctor format
+    #[arg(long)]
+    trace: Option<String>,
+
+    /// Only trace instructions in this hex PC range, e.g. 0150-01FF
+    #[arg(long, value_parser = parse_hex_range, requires = "trace")]
+    trace_range: Option<RangeInclusive<u16>>,
+
+    /// Only trace instructions in this ROM bank (hex, 0 for 0000-3FFF)
+    #[arg(long, value_parser = parse_bank, requires = "trace")]
+    trace_bank: Option<usize>,
+
+    /// Stop tracing after this many instructions
+    #[arg(long, requires = "trace")]
+    trace_limit: Option<u64>,
+
+    /// Append the label of every traced instruction (breaks gameboy-doctor compatibility)
+    #[arg(long, default_value_t = false, requires = "trace")]
+    trace_labels: bool,
+
+    /// RGBDS symbol file, defaults to <rom>.sym. <rom>.map is loaded too when it exists
+    #[arg(long)]
+    symbols: Option<String>,
+
+    /// Profile cycles per routine and write the report to this file on exit
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Write the profile as collapsed stacks (flamegraph input) to this file on exit
+    #[arg(long)]
+    profile_stacks: Option<String>,
+
+    /// Log interrupts, OAM DMA, bank switches, LCD register writes, timer overflows and HALT to this file as JSON lines
+    #[arg(long)]
+    events: Option<String>,
+
+    /// Load this save state before starting, a recorded movie starts from it
+    #[arg(long)]
+    load_state: Option<String>,
+
+    /// Record the joypad of every frame to this movie file, written on exit
+    #[arg(long)]
+    record_movie: Option<String>,
+
+    /// Play back this movie, read-only unless --record-movie is given too
+    #[arg(long)]
+    play_movie: Option<String>,
+}
+
+fn parse_hex(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16).map_err(|err| format!("invalid address {value}: {err}"))
+}
+
+fn parse_hex_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("invalid range {value}, expected start-end"))?;
+    Ok(parse_hex(start)?..=parse_hex(end)?)
+}
+
+fn parse_bank(value: &str) -> Result<usize, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches('$');
+    usize::from_str_radix(digits, 16).map_err(|err| format!("invalid bank {value}: {err}"))
+}
+
+fn exit_with_error(context: &str, err: std::io::Error) -> ! {
+    eprintln!("{context}: {err}");
+    std::process::exit(1);
+}
+
+fn run_headless(args: &Args) -> ! {
+    let palettes = Config::load(&args.config, &args.rom)
+        .and_then(|config| load_palettes(args, &config))
+        .unwrap_or_else(|err| exit_with_error("Invalid config", err));
+
+    let mut gameboy = create_gameboy(args, &palettes)
+        .unwrap_or_else(|err| exit_with_error("Failed to start", err));
+
+    let options = HeadlessOptions {
+        frames: args.frames,
+        until_pc: args.until_pc,
+        until_breakpoint: args.until_breakpoint,
+        output: args.output.clone(),
+        hash_log: args.hash_log.clone(),
+    };
+
+    start_logs(args, &mut gameboy).unwrap_or_else(|err| exit_with_error("Failed to start", err));
+    start_movie(args, &mut gameboy).unwrap_or_else(|err| exit_with_error("Failed to start", err));
+
+    let result = headless::run(&mut gameboy, &options);
+    finish_recordings(args, &mut gameboy);
+
+    match result {
+        Ok(reason) => std::process::exit(reason.exit_code()),
+        Err(err) => exit_with_error("Headless run failed", err),
+    }
+}
+
+//Trace, event log, movie and profile are only complete once the run is over
+fn finish_recordings(args: &Args, gameboy: &mut Gameboy) {
+    if let Err(err) = gameboy.stop_trace() {
+        eprintln!("Failed to save trace: {err}");
+    }
+
+    if let Err(err) = gameboy.stop_event_log() {
+        eprintln!("Failed to save event log: {err}");
+    }
+
+    let movie = gameboy.stop_movie();
+    if let (Some(movie), Some(path)) = (movie, &args.record_movie) {
+        if let Err(err) = movie.save(path) {
+            eprintln!("Failed to save movie: {err}");
+        }
+    }
+
+    let Some(profiler) = gameboy.stop_profiler() else {
+        return;
+    };
+
+    if let Some(path) = &args.profile {
+        if let Err(err) = profiler.write_report(path, gameboy.symbols()) {
+            eprintln!("Failed to save profile: {err}");
+        }
+    }
+    if let Some(path) = &args.profile_stacks {
+        if let Err(err) = profiler.write_collapsed_stacks(path, gameboy.symbols()) {
+            eprintln!("Failed to save profile: {err}");
+        }
+    }
+}
+
+fn create_gameboy(args: &Args, palettes: &DisplayPalettes) -> std::io::Result<Gameboy> {
+    let mut gameboy = Gameboy::new(&args.rom)?;
+    gameboy.set_model(args.model);
+    gameboy.set_display_palette(palettes.current().1);
+
+    if !args.bootrom.is_empty() {
+        gameboy.load_bootrom(&args.bootrom)?;
+    } else if args.builtin_boot {
+        gameboy.start_boot_sequence();
+    } else {
+        gameboy.no_bootrom_init();
+    }
+
+    let cheats = match &args.cheats {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(Path::new(&args.rom).with_extension("cht")).filter(|path| path.exists()),
+    };
+    if let Some(path) = cheats {
+        gameboy.set_cheats(load_cheats(&path)?);
+    }
+
+    gameboy.set_symbols(load_symbols(args)?);
+
+    if args.profile.is_some() || args.profile_stacks.is_some() {
+        gameboy.start_profiler();
+    }
+
+    Ok(gameboy)
+}
+
+//Only done once at startup, a reset carries the files over to the new machine
+fn start_logs(args: &Args, gameboy: &mut Gameboy) -> std::io::Result<()> {
+    if let Some(path) = &args.trace {
+        let filter = TraceFilter {
+            range: args.trace_range.clone(),
+            bank: args.trace_bank,
+            limit: args.trace_limit,
+        };
+        gameboy.start_trace(path, filter, args.trace_labels)?;
+    }
+
+    if let Some(path) = &args.events {
+        gameboy.start_event_log(path)?;
+    }
+
+    Ok(())
+}
+
+//Only done once at startup, movies can't survive a reset
+fn start_movie(args: &Args, gameboy: &mut Gameboy) -> std::io::Result<()> {
+    if let Some(path) = &args.load_state {
+        gameboy.load_state_file(path)?;
+    }
+
+    match (&args.play_movie, &args.record_movie) {
+        (Some(path), record) => gameboy.play_movie(Movie::load(path)?, record.is_none()),
+        (None, Some(_)) => gameboy.record_movie(),
+        (None, None) => Ok(()),
+    }
+}
+
+fn load_symbols(args: &Args) -> std::io::Result<Symbols> {
+    let sym = match &args.symbols {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(Path::new(&args.rom).with_extension("sym")).filter(|path| path.exists()),
+    };
+    let map = sym
+        .as_deref()
+        .unwrap_or(Path::new(&args.rom))
+        .with_extension("map");
+
+    let mut symbols = Symbols::default();
+    if let Some(path) = &sym {
+        symbols.load_sym(path)?;
+    }
+    if map.exists() {
+        symbols.load_map(&map)?;
+    }
+
+    Ok(symbols)
+}
+
+//The command line palette wins over the one in the config
+fn load_palettes(args: &Args, config: &Config) -> std::io::Result<DisplayPalettes> {
+    let mut palettes = DisplayPalettes::new(config)?;
+    if let Some(name) = &args.palette {
+        palettes.select(name)?;
+    }
+
+    Ok(palettes)
+}
+
+fn load_config(args: &Args) -> std::io::Result<(Config, Input, DisplayPalettes)> {
+    let config = Config::load(&args.config, &args.rom)?;
+    let input = Input::new(&config)?;
+    let palettes = load_palettes(args, &config)?;
+
+    Ok((config, input, palettes))
+}
+
+fn take_screenshot(gameboy: &Gameboy, config: &Config) -> std::io::Result<String> {
+    let path = screenshot_path(&config.screenshot_dir, &gameboy.header.title_string())?;
+    let path = path.to_string_lossy().into_owned();
+    gameboy.save_screenshot(&path)?;
+
+    Ok(path)
+}
+
+//Slots live next to the rom, e.g. roms/tetris.gb -> roms/tetris.ss3
+fn state_path(rom: &str, slot: u8) -> String {
+    Path::new(rom)
+        .with_extension(format!("ss{slot}"))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn start_rewind(gameboy: &mut Gameboy, config: &RewindConfig) {
+    if config.buffer_mb == 0 {
+        gameboy.stop_rewind();
+    } else {
+        gameboy.start_rewind(config.interval, config.buffer_mb * 1024 * 1024);
+    }
+}
+
+//Time runs backwards at normal speed, a snapshot is loaded every interval host frames.
+//Returns how many frames it went back
+fn rewind_frame(gameboy: &mut Gameboy, held_frames: u32) -> u64 {
+    let interval = gameboy
+        .rewind_buffer()
+        .map_or(1, |rewind| rewind.interval());
+    if !held_frames.is_multiple_of(interval) {
+        return 0;
+    }
+
+    match gameboy.rewind() {
+        Ok(true) => interval as u64,
+        Ok(false) => 0,
+        Err(err) => {
+            error!(target: "GB", "Failed to rewind: {err}");
+            0
+        }
+    }
+}
+
+//Returns false when the debugger stopped emulation partway through
+fn run_frame(gameboy: &mut Gameboy, debugger: &mut Option<Debugger>) -> bool {
+    let completed = match debugger {
+        Some(debugger) => debugger.run_frame(gameboy),
+        //Nothing sets breakpoints without the debugger
+        None => {
+            gameboy.run_frame();
+            true
+        }
+    };
+
+    if completed {
+        gameboy.record_rewind_frame();
+    }
+    completed
+}
+
+fn update_title(screen: &mut Screen, gameboy: &Gameboy, speed: &SpeedControl, percent: u64) {
+    screen.set_status(&format!(
+        "{} - {} ({}%)",
+        gameboy.header.title_string(),
+        speed.label(),
+        percent
+    ));
 }
 
 fn main() {
     let args = Args::parse();
 
-    if args.logging {
+    //Log lines would tear through the debugger's terminal UI
+    if args.logging && !args.debug {
         env_logger::init();
     }
 
-    let mut gameboy = Gameboy::new(&args.rom).unwrap();
+    if args.headless {
+        run_headless(&args);
+    }
 
-    let mut screen = Screen::default();
+    let (mut config, mut input, mut palettes) =
+        load_config(&args).unwrap_or_else(|err| exit_with_error("Invalid config", err));
 
-    // debug!("{}", args.bootrom);
+    let mut gameboy = create_gameboy(&args, &palettes)
+        .unwrap_or_else(|err| exit_with_error("Failed to start", err));
+    start_rewind(&mut gameboy, &config.rewind);
+    start_logs(&args, &mut gameboy).unwrap_or_else(|err| exit_with_error("Failed to start", err));
+    start_movie(&args, &mut gameboy).unwrap_or_else(|err| exit_with_error("Failed to start", err));
 
-    if args.bootrom.is_empty() {
-        gameboy.no_bootrom_init();
+    let mut screen = Screen::new(&config.display);
+    let mut osd = Osd::new(&config.osd);
+    let mut viewers = Viewers::new(&args.view)
+        .unwrap_or_else(|err| exit_with_error("Failed to open viewer", err));
+
+    let mut debugger = if args.debug {
+        Some(Debugger::new().unwrap_or_else(|err| exit_with_error("Failed to open debugger", err)))
     } else {
-        // let _ = gameboy.memory.load_rom(&args.bootrom);
-    }
+        None
+    };
+
+    let mut speed = SpeedControl::new(
+        args.speed,
+        args.turbo_speed.unwrap_or(config.turbo_speed),
+        args.start_paused,
+    );
 
     let mut fps = 0;
-    screen.window.set_target_fps(60);
-    let cycle_cap: u128 = 69905;
+    let mut frame_count: u64 = 0;
+    let mut slot: u8 = 0;
+    //Host frames the rewind hotkey has been held for
+    let mut rewind_frames: u32 = 0;
+    let mut movie_status = gameboy.movie_status();
+    //Pacing is done by FramePacer, minifb must not sleep on its own
+    screen.window.set_target_fps(0);
+    let mut pacer = FramePacer::new();
 
     let mut current_time = Instant::now();
     let mut track_cycle: u128 = 0;
+    let mut percent = 100;
+    update_title(&mut screen, &gameboy, &speed, percent);
+
+    'running: while screen.window.is_open() {
+        let mut speed_changed = false;
+        let mut announce_speed = false;
+
+        for hotkey in input.pressed_hotkeys(&screen.window) {
+            match hotkey {
+                Hotkey::Quit => break 'running,
+                Hotkey::Pause => {
+                    speed.toggle_pause();
+                    speed_changed = true;
+                    announce_speed = true;
+                }
+                Hotkey::FrameAdvance => {
+                    speed.advance_frame();
+                    speed_changed = true;
+                }
+                Hotkey::Turbo => {
+                    speed.toggle_turbo();
+                    speed_changed = true;
+                    announce_speed = true;
+                }
+                Hotkey::SlowMotion => {
+                    speed.cycle_slow_motion();
+                    speed_changed = true;
+                    announce_speed = true;
+                }
+                Hotkey::Reset if movie_status.is_some() => {
+                    osd.message("Can't reset during a movie");
+                }
+                Hotkey::Reset => match create_gameboy(&args, &palettes) {
+                    Ok(mut new_gameboy) => {
+                        new_gameboy.take_logs(&mut gameboy);
+                        gameboy = new_gameboy;
+                        start_rewind(&mut gameboy, &config.rewind);
+                        frame_count = 0;
+                        osd.message("Reset");
+                    }
+                    Err(err) => {
+                        error!(target: "GB", "Failed to reset: {err}");
+                        osd.message("Reset failed");
+                    }
+                },
+                Hotkey::ReloadConfig => match load_config(&args) {
+                    Ok((new_config, new_input, new_palettes)) => {
+                        if new_config.rewind != config.rewind {
+                            start_rewind(&mut gameboy, &new_config.rewind);
+                        }
+                        config = new_config;
+                        input = new_input;
+                        palettes = new_palettes;
+                        gameboy.set_display_palette(palettes.current().1);
+                        screen.apply_config(&config.display);
+                        speed.turbo_speed = args.turbo_speed.unwrap_or(config.turbo_speed);
+                        osd.config = config.osd.clone();
+                        osd.message("Config reloaded");
+                    }
+                    Err(err) => {
+                        error!(target: "GB", "Invalid config, keeping old bindings: {err}");
+                        osd.message("Invalid config");
+                    }
+                },
+                Hotkey::Screenshot => match take_screenshot(&gameboy, &config) {
+                    Ok(path) => osd.message(format!("Saved {path}")),
+                    Err(err) => {
+                        error!(target: "GB", "Failed to save screenshot: {err}");
+                        osd.message("Screenshot failed");
+                    }
+                },
+                Hotkey::NextPalette => {
+                    let (name, palette) = palettes.cycle();
+                    if gameboy.set_display_palette(palette) {
+                        osd.message(format!("Palette: {name}"));
+                    } else {
+                        osd.message("Palette is picked by the CGB boot rom");
+                    }
+                }
+                Hotkey::ToggleBg | Hotkey::ToggleWindow | Hotkey::ToggleSprites => {
+                    let mut ppu = gameboy.ppu.borrow_mut();
+                    let (name, shown) = match hotkey {
+                        Hotkey::ToggleBg => ("Background", &mut ppu.layers.bg),
+                        Hotkey::ToggleWindow => ("Window", &mut ppu.layers.window),
+                        _ => ("Sprites", &mut ppu.layers.sprites),
+                    };
+                    *shown = !*shown;
+                    osd.message(format!(
+                        "{name} {}",
+                        if *shown { "shown" } else { "hidden" }
+                    ));
+                }
+                Hotkey::TintLayers => {
+                    let mut ppu = gameboy.ppu.borrow_mut();
+                    ppu.layers.tint = !ppu.layers.tint;
+                    osd.message(if ppu.layers.tint {
+                        "Layer tint on"
+                    } else {
+                        "Layer tint off"
+                    });
+                }
+                Hotkey::ToggleCheats => {
+                    let enabled = !gameboy.cheats_enabled();
+                    gameboy.set_cheats_enabled(enabled);
+                    osd.message(if enabled { "Cheats on" } else { "Cheats off" });
+                }
+                Hotkey::SelectSlot(selected) => {
+                    slot = selected;
+                    osd.message(format!("Slot {slot}"));
+                }
+                Hotkey::SaveState => match gameboy.save_state_file(&state_path(&args.rom, slot)) {
+                    Ok(()) => osd.message(format!("Saved slot {slot}")),
+                    Err(err) => {
+                        error!(target: "GB", "Failed to save state: {err}");
+                        osd.message("Save failed");
+                    }
+                },
+                Hotkey::LoadState => match gameboy.load_state_file(&state_path(&args.rom, slot)) {
+                    Ok(()) => osd.message(format!("Loaded slot {slot}")),
+                    Err(err) => {
+                        error!(target: "GB", "Failed to load state: {err}");
+                        osd.message(format!("Can't load slot {slot}"));
+                    }
+                },
+                Hotkey::FastForward | Hotkey::Rewind => (),
+            }
+        }
+
+        let fast_forward = input.is_hotkey_down(&screen.window, Hotkey::FastForward);
+        if fast_forward != speed.fast_forward {
+            speed.fast_forward = fast_forward;
+            speed_changed = true;
+            announce_speed = true;
+        }
+
+        if speed_changed {
+            update_title(&mut screen, &gameboy, &speed, percent);
+        }
+
+        if announce_speed {
+            osd.message(speed.label());
+        }
+
+        if let Some(debugger) = &mut debugger {
+            match debugger.update(&mut gameboy) {
+                Ok(DebugAction::Quit) => break 'running,
+                Ok(DebugAction::None) => (),
+                Err(err) => error!(target: "GB", "Debugger failed: {err}"),
+            }
+        }
+
+        if !gameboy.is_playing_movie() {
+            input.update_joypad(&screen.window, &mut gameboy.joypad.borrow_mut());
+        }
+        osd.set_buttons(&gameboy.joypad.borrow());
+
+        let rewinding = input.is_hotkey_down(&screen.window, Hotkey::Rewind)
+            && gameboy.rewind_buffer().is_some();
+        if !rewinding {
+            rewind_frames = 0;
+        }
 
-    while screen.window.is_open() && !screen.window.is_key_down(minifb::Key::Escape) {
-        while gameboy.accum_cycle < cycle_cap {
-            //A = START
-            //S = select
-            //A = Z
-            //B = X
+        if rewinding {
+            if rewind_frames == 0 {
+                osd.message("Rewind");
+            }
+            frame_count = frame_count.saturating_sub(rewind_frame(&mut gameboy, rewind_frames));
+            rewind_frames += 1;
+        }
 
-            let mut joypad = gameboy.joypad.borrow_mut();
-            joypad.start = !screen.window.is_key_down(minifb::Key::A);
-            joypad.select = !screen.window.is_key_down(minifb::Key::S);
-            joypad.btn_a = !screen.window.is_key_down(minifb::Key::Z);
-            joypad.btn_b = !screen.window.is_key_down(minifb::Key::X);
-            joypad.up = !screen.window.is_key_down(minifb::Key::Up);
-            joypad.down = !screen.window.is_key_down(minifb::Key::Down);
-            joypad.left = !screen.window.is_key_down(minifb::Key::Left);
-            joypad.right = !screen.window.is_key_down(minifb::Key::Right);
-            drop(joypad);
+        let budget = speed.budget();
+        let frames = match budget {
+            _ if rewinding => 0,
+            FrameBudget::Frames(frames) => {
+                let mut completed = 0;
+                while completed < frames && run_frame(&mut gameboy, &mut debugger) {
+                    completed += 1;
+                }
+                completed as u64
+            }
+            FrameBudget::Unlimited => {
+                let budget_start = Instant::now();
+                let mut frames = 0;
+                while budget_start.elapsed() < FAST_FORWARD_BUDGET
+                    && run_frame(&mut gameboy, &mut debugger)
+                {
+                    frames += 1;
+                }
+                frames
+            }
+        };
+        track_cycle += frames as u128 * CYCLES_PER_FRAME;
 
-            gameboy.tick();
+        //The CGB boot rom reads the palette combo while the logo is shown
+        if frame_count < BOOT_PALETTE_FRAMES && gameboy.select_manual_palette() {
+            osd.message("Manual palette selected");
         }
 
+        frame_count += frames;
+        osd.set_frame(frame_count);
+
+        let status = gameboy.movie_status();
+        if let (Some(old), Some(new)) = (movie_status, status) {
+            match (old.desync, new.desync) {
+                (None, Some(frame)) => osd.message(format!("Movie desynced at frame {frame}")),
+                _ if new.mode != old.mode => osd.message(match new.mode {
+                    MovieMode::Recording => "Movie recording",
+                    MovieMode::Playing { .. } => "Movie playing",
+                    MovieMode::Finished => "Movie finished",
+                }),
+                _ => (),
+            }
+        }
+        movie_status = status;
+
         if gameboy.can_render {
-            screen.render(gameboy.get_frame_buffer());
+            screen.render(&gameboy.get_rgb_frame_buffer(), &mut osd);
             fps += 1;
         }
 
-        track_cycle += gameboy.accum_cycle;
-        gameboy.accum_cycle -= cycle_cap;
+        if let Some(view) = viewers.update(&gameboy, &input) {
+            match Viewers::export(view, &gameboy, &config.screenshot_dir) {
+                Ok(path) => osd.message(format!("Saved {path}")),
+                Err(err) => {
+                    error!(target: "GB", "Failed to export viewer: {err}");
+                    osd.message("Export failed");
+                }
+            }
+        }
+
+        //Host frames follow the hardware refresh rate whatever the speed, except when fast forwarding
+        match budget {
+            FrameBudget::Frames(_) => pacer.wait_frame(),
+            FrameBudget::Unlimited => pacer.resync(),
+        }
 
         if current_time.elapsed() >= Duration::from_secs(1) {
+            let elapsed = current_time.elapsed().as_secs_f64();
+            percent = (track_cycle as f64 / elapsed / CLOCK_SPEED as f64 * 100.0).round() as u64;
+            update_title(&mut screen, &gameboy, &speed, percent);
+            osd.set_speed(fps, percent);
+
             let cycle = track_cycle.to_formatted_string(&Locale::en);
-            info!(target:"GB", "{cycle} T-cycle, {fps} FPS");
+            info!(target:"GB", "{cycle} T-cycle, {fps} FPS, {percent}% speed");
             track_cycle = 0;
             fps = 0;
-            gameboy.accum_cycle = 0;
             current_time = Instant::now();
         }
     }
+
+    finish_recordings(&args, &mut gameboy);
 }
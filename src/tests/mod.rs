@@ -1,3 +1,2 @@
 pub mod rom_test;
 pub mod serde_helper;
-pub mod singlestep_tests;
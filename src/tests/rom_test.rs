@@ -1,26 +1,30 @@
-use crate::emulator::gameboy::Gameboy;
+use crate::emulator::gameboy::{Gameboy, CLOCK_SPEED};
 
 macro_rules! rom_test {
     ($($name:ident => $file:literal),*) => {
         $(
             #[test]
             fn $name() {
-                let mut gameboy = Gameboy::new(&format!("./roms/{}", $file)).unwrap();
+                let mut gameboy = Gameboy::new(&format!("./roms/{}.gb", $file)).unwrap();
+                gameboy.no_bootrom_init();
 
                 loop {
                     gameboy.tick();
 
-                    if gameboy.memory.read_byte(gameboy.cpu.pc) == 0x40 {
+                    //Mooneye tests end on LD B,B, give up on ones that never get there
+                    let pc = gameboy.cpu.borrow().pc;
+                    if gameboy.bus.peek_byte(pc) == 0x40 || gameboy.cycles() as u128 > CLOCK_SPEED * 30 {
                         break;
                     }
                 }
 
-                assert_eq!(gameboy.cpu.b, 3);
-                assert_eq!(gameboy.cpu.c, 5);
-                assert_eq!(gameboy.cpu.d, 8);
-                assert_eq!(gameboy.cpu.e, 13);
-                assert_eq!(gameboy.cpu.h, 21);
-                assert_eq!(gameboy.cpu.l, 34);
+                let cpu = gameboy.cpu.borrow();
+                assert_eq!(cpu.b, 3);
+                assert_eq!(cpu.c, 5);
+                assert_eq!(cpu.d, 8);
+                assert_eq!(cpu.e, 13);
+                assert_eq!(cpu.h, 21);
+                assert_eq!(cpu.l, 34);
             }
         )*
     };